@@ -0,0 +1,531 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use iced::{
+    futures::{channel::mpsc::Sender, never::Never, SinkExt},
+    Command,
+};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::{sleep, timeout},
+};
+
+use crate::{
+    message::Message,
+    player::{AccountInfo, AccountStatus},
+    server::{CrawlingStatus, ServerInfo},
+    ui::underworld::LureTarget,
+    Helper,
+};
+
+/// The largest request we are willing to read
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+const JSON_CONTENT: &str = "application/json";
+/// How long to wait after a failed accept. This doubles with every error in
+/// a row, up to the max
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    /// The address the API & metrics listen on. This is localhost by
+    /// default. Use `0.0.0.0` to let a Prometheus on another machine scrape
+    /// the metrics
+    pub bind: IpAddr,
+    /// The port, that the API listens on
+    pub port: u16,
+    /// Every request, including the ones for `/metrics`, has to send this as
+    /// a bearer token (`Authorization: Bearer <token>`). The API refuses all
+    /// requests, if this is empty
+    pub token: String,
+    /// Exports counters about crawling & the characters at `/metrics` in the
+    /// Prometheus text format
+    pub metrics: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8329,
+            token: String::new(),
+            metrics: false,
+        }
+    }
+}
+
+/// Creates a new random token to authenticate against the API
+pub fn generate_token() -> String {
+    let mut rng = fastrand::Rng::new();
+    (0..32).map(|_| rng.alphanumeric()).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: String,
+    /// The parts of the path, i.e. `["api", "accounts"]` for `/api/accounts`
+    pub path: Vec<String>,
+    pub body: Value,
+    /// The bearer token, that came with the request
+    pub token: Option<String>,
+}
+
+/// The connection a request came from. The response is written to this once
+/// the request has been handled
+#[derive(Debug, Clone)]
+pub struct ApiConnection(Arc<Mutex<Option<TcpStream>>>);
+
+impl ApiConnection {
+    pub fn respond(&self, status: u16, body: Value) -> Command<Message> {
+        self.respond_text(status, JSON_CONTENT, body.to_string())
+    }
+
+    pub fn respond_text(
+        &self,
+        status: u16,
+        content_type: &'static str,
+        body: String,
+    ) -> Command<Message> {
+        let Some(stream) = self.0.lock().unwrap().take() else {
+            return Command::none();
+        };
+        Command::perform(
+            write_response(stream, status, content_type, body),
+            |_| Message::ApiResponded,
+        )
+    }
+}
+
+/// Accepts requests on the configured address and passes them on to the
+/// helper. Every connection is read in its own task, so that a slow client
+/// can not block the others
+pub async fn serve(addr: SocketAddr, output: Sender<Message>) -> Never {
+    let listener = loop {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => break listener,
+            Err(e) => {
+                warn!("Could not start API on {addr}: {e}");
+                sleep(Duration::from_secs(30)).await;
+            }
+        }
+    };
+    debug!("API listening on {addr}");
+    let mut backoff = MIN_ACCEPT_BACKOFF;
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                backoff = MIN_ACCEPT_BACKOFF;
+                tokio::spawn(handle_connection(stream, output.clone()));
+            }
+            Err(e) => {
+                // Errors like running out of file descriptors would fail
+                // again right away, so we give them some time to resolve
+                warn!("Could not accept API connection: {e}");
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, mut output: Sender<Message>) {
+    let request =
+        timeout(Duration::from_secs(5), read_request(&mut stream)).await;
+    let request = match request {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            let body = json!({ "error": e }).to_string();
+            _ = write_response(stream, 400, JSON_CONTENT, body).await;
+            return;
+        }
+        Err(_) => return,
+    };
+    _ = output
+        .send(Message::ApiRequest {
+            request,
+            conn: ApiConnection(Arc::new(Mutex::new(Some(stream)))),
+        })
+        .await;
+}
+
+/// Checks the bearer token of a request in constant time, so that the token
+/// can not be guessed byte by byte. An empty token never matches
+pub fn token_matches(expected: &str, given: Option<&str>) -> bool {
+    let Some(given) = given else {
+        return false;
+    };
+    if expected.is_empty() || expected.len() != given.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Reads a single HTTP request and the bearer token, that came with it
+async fn read_request(stream: &mut TcpStream) -> Result<ApiRequest, String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err("connection closed".to_string());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+        if let Some(pos) = buf.windows(4).position(|a| a == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err("request too large".to_string());
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_uppercase();
+    let target = request_line.next().unwrap_or_default();
+    let path = target
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|a| !a.is_empty())
+        .map(|a| a.to_string())
+        .collect();
+
+    let mut content_length = 0;
+    let mut token = None;
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "content-length" => {
+                content_length = value.parse().map_err(|_| "invalid length")?;
+            }
+            "authorization" => {
+                token = value.strip_prefix("Bearer ").map(|a| a.to_string());
+            }
+            _ => {}
+        }
+    }
+    if content_length > MAX_REQUEST_SIZE {
+        return Err("request too large".to_string());
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+    let body = match body.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_slice(&body).map_err(|e| e.to_string())?,
+    };
+
+    Ok(ApiRequest {
+        method,
+        path,
+        body,
+        token,
+    })
+}
+
+async fn write_response(
+    mut stream: TcpStream,
+    status: u16,
+    content_type: &str,
+    body: String,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: \
+         {content_type}\r\nContent-Length: {}\r\nConnection: \
+         close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn not_found() -> (u16, Value) {
+    (404, json!({ "error": "not found" }))
+}
+
+fn accepted() -> (u16, Value) {
+    (202, json!({ "ok": true }))
+}
+
+fn account_json(server: &ServerInfo, account: &AccountInfo) -> Value {
+    let (status, detail, character) = match &*account.status.lock().unwrap() {
+        AccountStatus::LoggingIn => ("logging_in", None, None),
+        AccountStatus::Idle(_, gs) => (
+            "idle",
+            None,
+            Some((gs.character.level, gs.character.mushrooms)),
+        ),
+        AccountStatus::Busy(gs, reason) => (
+            "busy",
+            Some(reason.to_string()),
+            Some((gs.character.level, gs.character.mushrooms)),
+        ),
+        AccountStatus::FatalError(e) => ("error", Some(e.clone()), None),
+        AccountStatus::LoggingInAgain => ("logging_in_again", None, None),
+    };
+    json!({
+        "server": server.ident.ident,
+        "name": account.name,
+        "status": status,
+        "status_detail": detail,
+        "level": character.map(|a| a.0),
+        "mushrooms": character.map(|a| a.1),
+        "scrapbook_items": account
+            .scrapbook_info
+            .as_ref()
+            .map(|a| a.scrapbook.items.len()),
+        "auto_battle": account.scrapbook_info.as_ref().map(|a| a.auto_battle),
+        "auto_battle_stopped": account
+            .scrapbook_info
+            .as_ref()
+            .and_then(|a| a.stop_reason.clone()),
+        "auto_lure": account.underworld_info.as_ref().map(|a| a.auto_lure),
+    })
+}
+
+fn server_json(server: &ServerInfo) -> Value {
+    match &server.crawling {
+        CrawlingStatus::Waiting => {
+            json!({ "server": server.ident.ident, "status": "waiting" })
+        }
+        CrawlingStatus::Restoring => {
+            json!({ "server": server.ident.ident, "status": "restoring" })
+        }
+        CrawlingStatus::CrawlingFailed(e) => json!({
+            "server": server.ident.ident,
+            "status": "failed",
+            "error": e,
+        }),
+        CrawlingStatus::Crawling {
+            threads,
+            que,
+            player_info,
+            last_update,
+            recent_failures,
+            ..
+        } => {
+            let remaining = que.lock().unwrap().count_remaining();
+            json!({
+                "server": server.ident.ident,
+                "url": server.ident.url,
+                "status": "crawling",
+                "threads": threads,
+                "characters": player_info.len(),
+                "remaining": remaining,
+                "recent_failures": recent_failures.len(),
+                "last_update": last_update.to_rfc3339(),
+            })
+        }
+    }
+}
+
+impl Helper {
+    pub fn handle_api(
+        &mut self,
+        request: ApiRequest,
+    ) -> (u16, Value, Command<Message>) {
+        let path: Vec<&str> = request.path.iter().map(|a| a.as_str()).collect();
+        let ["api", path @ ..] = path.as_slice() else {
+            return with_none(not_found());
+        };
+        if !self.config.api.enabled {
+            return with_none(not_found());
+        }
+        let (status, body) = match (request.method.as_str(), path) {
+            ("GET", ["accounts"]) => {
+                let accounts: Vec<_> = self
+                    .servers
+                    .0
+                    .values()
+                    .flat_map(|s| {
+                        s.accounts.values().map(|a| account_json(s, a))
+                    })
+                    .collect();
+                (200, Value::Array(accounts))
+            }
+            ("GET", ["servers"]) => {
+                let servers =
+                    self.servers.0.values().map(server_json).collect();
+                (200, Value::Array(servers))
+            }
+            ("POST", ["servers", server, "save"]) => {
+                let Some(server) =
+                    self.servers.0.values().find(|a| a.ident.ident == *server)
+                else {
+                    return with_none(not_found());
+                };
+                let id = server.ident.id;
+                let (status, body) = accepted();
+                return (status, body, self.handle_msg(Message::SaveHoF(id)));
+            }
+            (method, ["accounts", server, name, rest @ ..]) => {
+                return self.handle_account_api(
+                    method, server, name, rest, &request.body,
+                );
+            }
+            _ => not_found(),
+        };
+        (status, body, Command::none())
+    }
+
+    fn handle_account_api(
+        &mut self,
+        method: &str,
+        server: &str,
+        name: &str,
+        path: &[&str],
+        body: &Value,
+    ) -> (u16, Value, Command<Message>) {
+        let name = name.to_lowercase();
+        let Some((server, account)) = self
+            .servers
+            .0
+            .values()
+            .filter(|a| a.ident.ident == server)
+            .find_map(|s| {
+                s.accounts.values().find(|a| a.name == name).map(|a| (s, a))
+            })
+        else {
+            return with_none(not_found());
+        };
+        let ident = account.ident;
+        let uid = body.get("uid").and_then(|a| a.as_u64());
+
+        let msg = match (method, path) {
+            ("GET", []) => {
+                return with_none((200, account_json(server, account)))
+            }
+            ("GET", ["targets"]) => {
+                let Some(si) = &account.scrapbook_info else {
+                    return with_none(not_found());
+                };
+                let targets = si
+                    .best
+                    .iter()
+                    .map(|a| {
+                        json!({
+                            "uid": a.info.uid,
+                            "name": a.info.name,
+                            "level": a.info.level,
+                            "missing": a.missing,
+                            "outdated": a.is_old(),
+                        })
+                    })
+                    .collect();
+                return with_none((200, Value::Array(targets)));
+            }
+            ("GET", ["lures"]) => {
+                let Some(ui) = &account.underworld_info else {
+                    return with_none(not_found());
+                };
+                let targets = ui
+                    .best
+                    .iter()
+                    .map(|a| {
+                        json!({
+                            "uid": a.info.uid,
+                            "name": a.info.name,
+                            "level": a.info.level,
+                            "items": a.info.equipment.len(),
+                            "success": a.success,
+                            "silver": a.estimate.map(|e| e.0),
+                            "souls": a.estimate.map(|e| e.1),
+                            "lured_before": a.previous.map(|p| p.total()),
+                            "won_before": a.previous.map(|p| p.won),
+                        })
+                    })
+                    .collect();
+                return with_none((200, Value::Array(targets)));
+            }
+            ("POST", ["attack"]) => {
+                let Some(si) = &account.scrapbook_info else {
+                    return with_none(not_found());
+                };
+                let target = match uid {
+                    Some(uid) => {
+                        si.best.iter().find(|a| u64::from(a.info.uid) == uid)
+                    }
+                    None => si.best.first(),
+                };
+                let Some(target) = target else {
+                    return with_none((409, json!({"error": "no target"})));
+                };
+                Message::PlayerAttack {
+                    ident,
+                    target: target.clone(),
+                }
+            }
+            ("POST", ["lure"]) => {
+                let Some(ui) = &account.underworld_info else {
+                    return with_none(not_found());
+                };
+                let target = match uid {
+                    Some(uid) => {
+                        ui.best.iter().find(|a| u64::from(a.info.uid) == uid)
+                    }
+                    None => ui.best.first(),
+                };
+                let Some(target) = target else {
+                    return with_none((409, json!({"error": "no target"})));
+                };
+                Message::PlayerLure {
+                    ident,
+                    target: LureTarget {
+                        uid: target.info.uid,
+                        name: target.info.name.clone(),
+                        level: target.info.level,
+                        items: target.info.equipment.len(),
+                    },
+                }
+            }
+            ("POST", ["auto-battle"]) => {
+                let Some(state) = body.get("enabled").and_then(|a| a.as_bool())
+                else {
+                    return with_none((400, json!({"error": "no state"})));
+                };
+                Message::AutoBattle { ident, state }
+            }
+            _ => return with_none(not_found()),
+        };
+        let (status, body) = accepted();
+        (status, body, self.handle_msg(msg))
+    }
+}
+
+fn with_none((status, body): (u16, Value)) -> (u16, Value, Command<Message>) {
+    (status, body, Command::none())
+}
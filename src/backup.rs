@@ -15,13 +15,14 @@ use tokio::{
 };
 
 use crate::{
-    handle_new_char_info, CharacterInfo, CrawlingOrder, CrawlingStatus, QueID,
-    WorkerQue,
+    config::NakedRules, handle_new_char_info, CharacterInfo, CrawlingOrder,
+    CrawlingStatus, QueID, WorkerQue,
 };
 
 pub async fn restore_backup(
     backup: Option<Box<ZHofBackup>>,
     total_pages: usize,
+    naked_rules: NakedRules,
 ) -> RestoreData {
     if backup.is_none() {
         debug!("Reset crawling progress");
@@ -65,7 +66,7 @@ pub async fn restore_backup(
             yield_now().await;
         }
         handle_new_char_info(
-            char, &mut equipment, &mut player_info, &mut naked,
+            char, &mut equipment, &mut player_info, &mut naked, &naked_rules,
         );
     }
 
@@ -123,6 +124,7 @@ impl RestoreData {
                 min_level: self.min_level,
                 lvl_skipped_accounts: self.lvl_skipped_accounts,
                 self_init: false,
+                paused: false,
             })),
             player_info: self.player_info,
             equipment: self.equipment,
@@ -197,8 +199,15 @@ fn default_max_lvl() -> u32 {
 
 impl ZHofBackup {
     pub async fn write(&self, ident: &str) -> Result<(), std::io::Error> {
+        self.write_path(format!("{}.zhof", ident)).await
+    }
+
+    pub async fn write_path(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), std::io::Error> {
         let serialized = serde_json::to_string(&self).unwrap();
-        let file = tokio::fs::File::create(format!("{}.zhof", ident)).await?;
+        let file = tokio::fs::File::create(path).await?;
         let mut encoder = ZlibEncoder::new(file);
         encoder.write_all(serialized.as_bytes()).await?;
         encoder.flush().await?;
@@ -207,7 +216,13 @@ impl ZHofBackup {
     }
 
     pub async fn read(ident: &str) -> Result<ZHofBackup, std::io::Error> {
-        let file = tokio::fs::File::open(format!("{}.zhof", ident)).await?;
+        Self::read_path(format!("{}.zhof", ident)).await
+    }
+
+    pub async fn read_path(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<ZHofBackup, std::io::Error> {
+        let file = tokio::fs::File::open(path).await?;
         let reader = BufReader::new(file);
         let mut decoder =
             async_compression::tokio::bufread::ZlibDecoder::new(reader);
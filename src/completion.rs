@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sf_api::gamestate::{
+    character::Class, items::EquipmentSlot, unlockables::EquipmentIdent,
+};
+
+use crate::item_weight;
+
+/// The width of the model id buckets shown in the completion view
+pub const MODEL_RANGE_SIZE: u16 = 10;
+
+/// Breakdown of how complete a scrapbook is, compared to the items that have
+/// been seen on the server
+#[derive(Debug, Default, Clone)]
+pub struct ScrapbookCompletion {
+    pub total: CompletionCount,
+    pub by_slot: HashMap<EquipmentSlot, CompletionCount>,
+    pub by_class: HashMap<Option<Class>, CompletionCount>,
+    /// Keyed by the first model id of the range
+    pub by_model: BTreeMap<u16, CompletionCount>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CompletionCount {
+    /// Items already in the scrapbook
+    pub found: usize,
+    /// Missing items, that at least one crawled player currently wears
+    pub obtainable: usize,
+    /// Missing items, that have been seen on the server before, but are not
+    /// worn by anyone anymore
+    pub unheld: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Availability {
+    Found,
+    Obtainable,
+    Unheld,
+}
+
+impl CompletionCount {
+    fn add(&mut self, availability: Availability) {
+        match availability {
+            Availability::Found => self.found += 1,
+            Availability::Obtainable => self.obtainable += 1,
+            Availability::Unheld => self.unheld += 1,
+        }
+    }
+
+    pub fn missing(&self) -> usize {
+        self.obtainable + self.unheld
+    }
+}
+
+impl ScrapbookCompletion {
+    fn add(&mut self, eq: &EquipmentIdent, availability: Availability) {
+        let range_start = eq.model_id / MODEL_RANGE_SIZE * MODEL_RANGE_SIZE;
+        for count in [
+            &mut self.total,
+            self.by_slot.entry(eq.typ).or_default(),
+            self.by_class.entry(eq.class).or_default(),
+            self.by_model.entry(range_start).or_default(),
+        ] {
+            count.add(availability);
+        }
+    }
+}
+
+pub fn calc_completion(
+    equipment: &HashMap<
+        EquipmentIdent,
+        HashSet<u32, ahash::RandomState>,
+        ahash::RandomState,
+    >,
+    scrapbook: &HashSet<EquipmentIdent>,
+    epic_weight: usize,
+) -> ScrapbookCompletion {
+    let mut res = ScrapbookCompletion::default();
+    // Items, that are not considered for targets are left out here as well
+    let is_counted = |eq: &EquipmentIdent| item_weight(eq, epic_weight) > 0;
+
+    for eq in scrapbook.iter().filter(|a| is_counted(a)) {
+        res.add(eq, Availability::Found);
+    }
+    for (eq, players) in equipment {
+        if scrapbook.contains(eq) || !is_counted(eq) {
+            continue;
+        }
+        // Players get removed from these sets when they are crawled again, so
+        // an empty set means, that nobody wears this item anymore
+        let availability = if players.is_empty() {
+            Availability::Unheld
+        } else {
+            Availability::Obtainable
+        };
+        res.add(eq, availability);
+    }
+    res
+}
@@ -1,15 +1,68 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Local};
 use iced::Theme;
+use nohash_hasher::IntMap;
 use num_format::CustomFormat;
 use serde::{Deserialize, Serialize};
-use sf_api::session::PWHash;
+use sf_api::{
+    gamestate::{
+        character::{Class, Gender, Race},
+        items::EquipmentSlot,
+    },
+    session::{PWHash, ServerConnection},
+};
+
+use crate::{
+    api::ApiConfig,
+    crawler::{CrawlerLogin, CrawlerRegistration, CrawlingOrder},
+    jobs::ScheduledJob,
+    notify::NotifyConfig,
+    schedule::Schedule,
+    select::ServerPattern,
+    server::ServerIdent,
+    vault::{self, Vault, VaultError, VaultHeader},
+    AttackTarget, CharacterInfo, ServerID,
+};
 
-use crate::{server::ServerIdent, ServerID};
+/// The version of the config format, that this version of the helper writes
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The migrations between versions of the config. The one at index `n` turns
+/// a config of version `n` into one of version `n + 1`
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[
+    // Crawlers registered before the crawler config existed use the reversed
+    // name as their password, so those have to keep trying that
+    |table| {
+        if !table.contains_key("crawler") {
+            let mut crawler = toml::Table::new();
+            crawler.insert("legacy_password".to_string(), true.into());
+            table.insert("crawler".to_string(), crawler.into());
+        }
+    },
+];
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    /// The version of the format. Configs without one are from before
+    /// versioning existed
+    #[serde(default)]
+    pub version: u32,
     pub accounts: Vec<AccountConfig>,
     pub theme: AvailableTheme,
     pub base_name: String,
+    /// How the crawlers log in
+    #[serde(default)]
+    pub crawler: CrawlerConfig,
     pub auto_fetch_newest: bool,
     #[serde(default)]
     pub auto_poll: bool,
@@ -23,11 +76,62 @@ pub struct Config {
     pub show_class_icons: bool,
     #[serde(default = "default_blacklist_threshhold")]
     pub blacklist_threshold: usize,
+    /// The amount of days after which players we lost against are attacked
+    /// again. 0 means, that they are never retried
+    #[serde(default)]
+    pub blacklist_expiry_days: u32,
+    #[serde(default)]
+    pub epic_policy: EpicPolicy,
+    #[serde(default = "default_epic_weight")]
+    pub epic_weight: usize,
+    #[serde(default)]
+    pub notifications: NotifyConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// The rules for which players count as naked (lure targets), keyed by
+    /// the server ident
+    #[serde(default)]
+    pub naked_rules: HashMap<String, NakedRules>,
+    /// Servers, that `crawl --all` skips. These are globs, or regexes
+    /// starting with `re:`, matched against the host of the server
+    #[serde(default = "default_crawl_exclude")]
+    pub crawl_exclude: Vec<String>,
+    /// Crawls, that the `schedule` command repeats
+    #[serde(default)]
+    pub crawl_jobs: Vec<CrawlJob>,
+    /// Set, if the password hashes of the accounts are encrypted with a
+    /// master password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<VaultHeader>,
+    /// The key for the password hashes. None, if there is no master password,
+    /// or if the config has not been unlocked yet
+    #[serde(skip)]
+    pub vault: Option<Vault>,
+    /// The file this config is read from and written to
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Set, once the file has been copied to `.bak`. This only happens on
+    /// the first write after reading the config, so that later writes can
+    /// not replace the backup of a good config with a bad one
+    #[serde(skip)]
+    pub backed_up: Arc<AtomicBool>,
+    /// The version the file on disk had, if it was migrated when reading it.
+    /// The first write keeps a copy of it as `.vN.bak`
+    #[serde(skip)]
+    pub migrated_from: Option<u32>,
+    /// A crawler password was generated while reading the config. It has to
+    /// be saved, once a crawler uses it
+    #[serde(skip)]
+    pub unsaved_password: bool,
 
     #[serde(default = "default_locale", skip)]
     pub num_format: CustomFormat,
 }
 
+fn default_crawl_exclude() -> Vec<String> {
+    vec!["speed.sfgame.net".to_string()]
+}
+
 fn default_threads() -> usize {
     10
 }
@@ -46,6 +150,10 @@ fn default_blacklist_threshhold() -> usize {
     2
 }
 
+fn default_epic_weight() -> usize {
+    2
+}
+
 fn default_class_icons() -> bool {
     true
 }
@@ -64,15 +172,34 @@ impl Default for Config {
         }
 
         Self {
+            version: CONFIG_VERSION,
             accounts: vec![],
             theme: AvailableTheme::Dark,
             base_name,
+            crawler: CrawlerConfig {
+                password: generate_crawler_password(),
+                ..Default::default()
+            },
             auto_fetch_newest: true,
             max_threads: default_threads(),
             auto_poll: false,
             show_crawling_restrict: false,
             show_class_icons: true,
             blacklist_threshold: default_blacklist_threshhold(),
+            blacklist_expiry_days: 0,
+            epic_policy: EpicPolicy::default(),
+            epic_weight: default_epic_weight(),
+            notifications: NotifyConfig::default(),
+            api: ApiConfig::default(),
+            naked_rules: HashMap::new(),
+            crawl_exclude: default_crawl_exclude(),
+            crawl_jobs: vec![],
+            encryption: None,
+            vault: None,
+            path: PathBuf::from("helper.toml"),
+            backed_up: Default::default(),
+            migrated_from: None,
+            unsaved_password: false,
             num_format: default_locale(),
             start_threads: default_start_threads(),
         }
@@ -184,17 +311,311 @@ impl Config {
         res
     }
 
+    pub fn naked_rules(&self, server_ident: &str) -> &NakedRules {
+        static DEFAULT_RULES: NakedRules = NakedRules {
+            limits: DEFAULT_NAKED_LIMITS,
+            empty_slots: Vec::new(),
+        };
+        self.naked_rules.get(server_ident).unwrap_or(&DEFAULT_RULES)
+    }
+
+    /// The amount an epic item should count for, when looking for new
+    /// scrapbook items. 0 means, that epics are ignored
+    pub fn epic_weight(&self) -> usize {
+        match self.epic_policy {
+            EpicPolicy::Ignore => 0,
+            EpicPolicy::Count => 1,
+            EpicPolicy::Weighted => self.epic_weight.max(1),
+        }
+    }
+
+    /// The directory the fight & lure history is stored in. Every profile
+    /// has its own one next to its config
+    pub fn history_dir(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|a| a.to_string_lossy().to_string())
+            .unwrap_or("helper".to_string());
+        self.path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(format!("{stem}_history"))
+    }
+
+    /// The master password has to be entered, before the accounts can be
+    /// used
+    pub fn is_locked(&self) -> bool {
+        self.encryption.is_some() && self.vault.is_none()
+    }
+
+    /// Decrypts the password hashes of all accounts. Hashes, that are still
+    /// stored in plain text, are encrypted on the next write. This derives
+    /// the key right away, so it should not be used from the UI thread
+    pub fn unlock(&mut self, password: &str) -> Result<(), VaultError> {
+        let Some(header) = &self.encryption else {
+            return Ok(());
+        };
+        let vault = Vault::unlock(password, header)?;
+        self.use_vault(vault)
+    }
+
+    /// Decrypts the password hashes of all accounts and the passwords of the
+    /// crawlers with an already unlocked vault
+    pub fn use_vault(&mut self, vault: Vault) -> Result<(), VaultError> {
+        let mut accounts = self.accounts.clone();
+        let mut has_plain = false;
+        for pw_hash in pw_hashes_mut(&mut accounts) {
+            if !vault::is_encrypted(pw_hash.get()) {
+                has_plain = true;
+                continue;
+            }
+            *pw_hash = PWHash::from_hash(vault.decrypt(pw_hash.get())?);
+        }
+        let mut crawler = self.crawler.clone();
+        for password in crawler.passwords_mut() {
+            if !vault::is_encrypted(password) {
+                has_plain = true;
+                continue;
+            }
+            *password = vault.decrypt(password)?;
+        }
+        self.accounts = accounts;
+        self.crawler = crawler;
+        self.vault = Some(vault);
+        if has_plain {
+            _ = self.write();
+        }
+        Ok(())
+    }
+
+    /// Encrypts the password hashes & crawler passwords with a new master
+    /// password from now on. The vault has to be created with
+    /// [`Vault::create`] first
+    pub fn set_master_password(
+        &mut self,
+        vault: Vault,
+        header: VaultHeader,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let old_vault = self.vault.replace(vault);
+        let old_header = self.encryption.replace(header);
+        if let Err(e) = self.write() {
+            self.vault = old_vault;
+            self.encryption = old_header;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Stores the password hashes & crawler passwords in plain text again
+    pub fn remove_master_password(&mut self) {
+        self.vault = None;
+        self.encryption = None;
+        _ = self.write();
+    }
+
     pub fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let str = toml::to_string_pretty(self)?;
-        std::fs::write("helper.toml", str)?;
+        let str = match &self.vault {
+            Some(vault) => {
+                let mut config = self.clone();
+                for pw_hash in pw_hashes_mut(&mut config.accounts) {
+                    if !vault::is_encrypted(pw_hash.get()) {
+                        *pw_hash =
+                            PWHash::from_hash(vault.encrypt(pw_hash.get())?);
+                    }
+                }
+                for password in config.crawler.passwords_mut() {
+                    if !vault::is_encrypted(password) {
+                        *password = vault.encrypt(password)?;
+                    }
+                }
+                toml::to_string_pretty(&config)?
+            }
+            None => toml::to_string_pretty(self)?,
+        };
+        // Keeps the state from before this session around, in case one of
+        // the writes loses something
+        if self.path.exists() && !self.backed_up.load(Ordering::Relaxed) {
+            std::fs::copy(&self.path, with_suffix(&self.path, ".bak"))?;
+            if let Some(version) = self.migrated_from {
+                let backup =
+                    with_suffix(&self.path, &format!(".v{version}.bak"));
+                std::fs::copy(&self.path, backup)?;
+            }
+            self.backed_up.store(true, Ordering::Relaxed);
+        }
+        let tmp = with_suffix(&self.path, ".tmp");
+        std::fs::write(&tmp, str)?;
+        std::fs::rename(&tmp, &self.path)?;
         Ok(())
     }
-    pub fn restore() -> Result<Self, Box<dyn std::error::Error>> {
-        let val = std::fs::read_to_string("helper.toml")?;
-        Ok(toml::from_str(&val)?)
+
+    /// Reads, migrates and validates the config. If the file does not exist
+    /// yet, a new config is created. Nothing is written here, the changes
+    /// from the migration are only saved by the next [`Config::write`]
+    pub fn restore(path: &Path) -> Result<Self, ConfigError> {
+        let val = match std::fs::read_to_string(path) {
+            Ok(val) => val,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Config {
+                    path: path.to_path_buf(),
+                    unsaved_password: true,
+                    ..Default::default()
+                });
+            }
+            Err(e) => return Err(ConfigError::Io(e)),
+        };
+        let mut table: toml::Table =
+            toml::from_str(&val).map_err(ConfigError::Parse)?;
+        let version = migrate(&mut table)?;
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(ConfigError::Parse)?;
+        config.path = path.to_path_buf();
+        if version < CONFIG_VERSION {
+            log::info!("Migrated the config from version {version}");
+            config.migrated_from = Some(version);
+        }
+
+        let problems = config.validate();
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+        // Configs from older versions have no crawler password yet. It is
+        // saved along with the next change
+        if config.crawler.password.is_empty() {
+            config.crawler.password = generate_crawler_password();
+            config.unsaved_password = true;
+        }
+        Ok(config)
+    }
+
+    /// Checks for values, that the helper can not work with. These can only
+    /// come from editing the file by hand
+    fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.base_name.trim().is_empty() {
+            problems.push("base_name is empty".to_string());
+        }
+        if self.start_threads > self.max_threads {
+            problems.push("start_threads is above max_threads".to_string());
+        }
+        let mut check_server = |name: &str, server: &str| {
+            if ServerConnection::new(server).is_none() {
+                problems
+                    .push(format!("{name} has an invalid server: {server}"));
+            }
+        };
+        for acc in &self.accounts {
+            match acc {
+                AccountConfig::Regular { name, server, .. } => {
+                    check_server(name, server);
+                }
+                AccountConfig::SF { characters, .. } => {
+                    for c in characters {
+                        check_server(&c.ident.name, &c.ident.server);
+                    }
+                }
+            }
+        }
+        for pattern in &self.crawl_exclude {
+            if let Err(e) = pattern.parse::<ServerPattern>() {
+                problems.push(e);
+            }
+        }
+        let mut job_names = HashSet::new();
+        for job in &self.crawl_jobs {
+            if !job_names.insert(&job.name) {
+                problems.push(format!("The job {} exists twice", job.name));
+            }
+            if let Err(e) = ScheduledJob::from_config(job) {
+                problems.push(e);
+            }
+            if let (Some(min), Some(max)) = (job.min_level, job.max_level) {
+                if min > max {
+                    problems.push(format!(
+                        "The job {} has a min_level above its max_level",
+                        job.name
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Runs all migrations, that the config needs and returns the version it had
+fn migrate(table: &mut toml::Table) -> Result<u32, ConfigError> {
+    let version = match table.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_integer()
+            .and_then(|a| u32::try_from(a).ok())
+            .ok_or_else(|| {
+                ConfigError::Invalid(vec![format!("Invalid version {version}")])
+            })?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::TooNew(version));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(table);
+    }
+    table.insert("version".to_string(), i64::from(CONFIG_VERSION).into());
+    Ok(version)
+}
+
+/// The path with something appended to the file name
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    /// The config was written by a newer version of the helper
+    TooNew(u32),
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Could not read the config: {e}"),
+            ConfigError::Parse(e) => {
+                write!(f, "Could not parse the config: {e}")
+            }
+            ConfigError::TooNew(version) => write!(
+                f,
+                "The config is from a newer version of the helper (format \
+                 {version}). Please update"
+            ),
+            ConfigError::Invalid(problems) => {
+                write!(f, "The config is invalid:")?;
+                for problem in problems {
+                    write!(f, "\n- {problem}")?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
+impl std::error::Error for ConfigError {
+}
+
+fn pw_hashes_mut(
+    accounts: &mut [AccountConfig],
+) -> impl Iterator<Item = &mut PWHash> {
+    accounts.iter_mut().map(|a| match a {
+        AccountConfig::Regular { pw_hash, .. }
+        | AccountConfig::SF { pw_hash, .. } => pw_hash,
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum AccountCreds {
@@ -261,6 +682,189 @@ pub struct CharacterConfig {
     pub auto_battle: bool,
     #[serde(default)]
     pub auto_lure: bool,
+    #[serde(default)]
+    pub blacklist: Vec<BlacklistEntry>,
+    #[serde(default)]
+    pub mushrooms: MushroomPolicy,
+    #[serde(default)]
+    pub battle_limits: BattleLimits,
+    #[serde(default)]
+    pub battle_schedule: Schedule,
+    #[serde(default)]
+    pub lure_schedule: Schedule,
+}
+
+/// Controls if, and how many mushrooms auto-battle is allowed to spend on
+/// fights, that happen before the next free fight is available
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq,
+)]
+pub struct MushroomPolicy {
+    /// The maximum amount of mushrooms spent per day. 0 means, that
+    /// auto-battle never uses mushrooms
+    #[serde(default)]
+    pub max_per_day: u32,
+    /// Only spend a mushroom, if the target has at least this many new items
+    #[serde(default)]
+    pub min_missing: usize,
+    /// The amount of mushrooms, that should never be spent
+    #[serde(default)]
+    pub reserve: u32,
+}
+
+impl MushroomPolicy {
+    /// Checks if a mushroom may be spent, ignoring the quality of the target
+    pub fn allows(&self, spent_today: u32, mushrooms: u32) -> bool {
+        self.max_per_day > 0
+            && spent_today < self.max_per_day
+            && mushrooms > self.reserve
+    }
+
+    /// Checks if a mushroom should be spent on a fight against a target with
+    /// this many new items
+    pub fn should_spend(
+        &self,
+        spent_today: u32,
+        mushrooms: u32,
+        missing: usize,
+    ) -> bool {
+        self.allows(spent_today, mushrooms) && missing >= self.min_missing
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlacklistEntry {
+    pub uid: u32,
+    pub name: String,
+    /// The amount of fights we have lost against this player
+    pub losses: usize,
+    pub last_loss: Option<DateTime<Local>>,
+    /// Manually banned players are never attacked and never expire
+    #[serde(default)]
+    pub manual: bool,
+}
+
+impl BlacklistEntry {
+    pub fn is_banned(&self, threshold: usize) -> bool {
+        self.manual || self.losses >= threshold.max(1)
+    }
+
+    pub fn is_expired(&self, expiry_days: u32) -> bool {
+        if self.manual || expiry_days == 0 {
+            return false;
+        }
+        let Some(last_loss) = self.last_loss else {
+            return false;
+        };
+        last_loss + chrono::Duration::days(expiry_days.into()) < Local::now()
+    }
+}
+
+/// Conditions, that make auto-battle skip targets, or stop entirely. A value of
+/// 0 disables the condition
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq,
+)]
+#[serde(default)]
+pub struct BattleLimits {
+    /// Skip targets, that have less new items than this
+    pub min_missing: usize,
+    /// Skip targets, that are more than this many levels above us
+    pub max_level_diff: u16,
+    /// Skip targets, that we have already lost against this many times
+    pub max_losses: usize,
+    /// Stop after losing this many fights in a row
+    pub max_consecutive_losses: u32,
+    /// Stop once the scrapbook contains this many items
+    pub target_items: usize,
+}
+
+impl BattleLimits {
+    /// Checks if auto-battle should fight against this target
+    pub fn allows(
+        &self,
+        target: &AttackTarget,
+        own_level: u16,
+        blacklist: &IntMap<u32, BlacklistEntry>,
+    ) -> bool {
+        if self.min_missing > 0 && target.missing < self.min_missing {
+            return false;
+        }
+        if self.max_level_diff > 0
+            && target.info.level > own_level.saturating_add(self.max_level_diff)
+        {
+            return false;
+        }
+        if self.max_losses > 0 {
+            if let Some(entry) = blacklist.get(&target.info.uid) {
+                if entry.losses >= self.max_losses {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// The reason, why auto-battle should stop, if it should
+    pub fn stop_reason(
+        &self,
+        consecutive_losses: u32,
+        scrapbook_items: usize,
+    ) -> Option<String> {
+        if self.max_consecutive_losses > 0
+            && consecutive_losses >= self.max_consecutive_losses
+        {
+            return Some(format!("Lost {consecutive_losses} fights in a row"));
+        }
+        if self.target_items > 0 && scrapbook_items >= self.target_items {
+            return Some(format!("Reached {} items", self.target_items));
+        }
+        None
+    }
+}
+
+/// Decides, which players are shown as lure targets in the underworld
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct NakedRules {
+    pub limits: NakedLimits,
+    /// Slots, that have to be empty
+    pub empty_slots: Vec<EquipmentSlot>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct NakedLimits {
+    /// The maximum amount of items a naked player can wear
+    pub max_items: usize,
+    pub min_level: u16,
+    /// 0 means, that there is no upper limit
+    pub max_level: u16,
+}
+
+const DEFAULT_NAKED_LIMITS: NakedLimits = NakedLimits {
+    max_items: 3,
+    min_level: 100,
+    max_level: 0,
+};
+
+impl Default for NakedLimits {
+    fn default() -> Self {
+        DEFAULT_NAKED_LIMITS
+    }
+}
+
+impl NakedRules {
+    pub fn matches(&self, char: &CharacterInfo) -> bool {
+        let limits = &self.limits;
+        char.equipment.len() <= limits.max_items
+            && char.level >= limits.min_level
+            && (limits.max_level == 0 || char.level <= limits.max_level)
+            && !char
+                .equipment
+                .iter()
+                .any(|eq| self.empty_slots.contains(&eq.typ))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
@@ -289,6 +893,38 @@ impl AccountConfig {
             },
         }
     }
+    /// Checks if this account, or any of its characters should be logged in
+    /// on startup
+    pub fn has_auto_login(&self) -> bool {
+        match self {
+            AccountConfig::Regular { config, .. } => config.login,
+            AccountConfig::SF { characters, .. } => {
+                characters.iter().any(|a| a.config.login)
+            }
+        }
+    }
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq,
+)]
+pub enum EpicPolicy {
+    #[default]
+    Ignore,
+    Count,
+    Weighted,
+}
+
+#[allow(clippy::to_string_trait_impl)]
+impl ToString for EpicPolicy {
+    fn to_string(&self) -> String {
+        match self {
+            EpicPolicy::Ignore => "Ignore",
+            EpicPolicy::Count => "Count",
+            EpicPolicy::Weighted => "Weighted",
+        }
+        .to_string()
+    }
 }
 
 #[derive(
@@ -379,3 +1015,361 @@ impl AvailableTheme {
         }
     }
 }
+
+/// A crawl, that is repeated on a schedule
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CrawlJob {
+    pub name: String,
+    /// When the job runs, in the cron format. `0 3 * * *` runs it every
+    /// night at 3
+    pub cron: String,
+    /// The servers to crawl. If this and `file` are empty, all servers are
+    /// crawled, except the ones in `crawl_exclude`
+    pub urls: Vec<String>,
+    /// A file with one server url per line
+    pub file: Option<PathBuf>,
+    /// Globs, or regexes starting with `re:`. Only servers matching one of
+    /// these are crawled
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// The amount of servers that are crawled at the same time
+    pub concurrency: usize,
+    /// The amount of threads per server
+    pub threads: usize,
+    /// Saves the progress and stops the run after this many minutes
+    pub max_runtime_minutes: Option<u64>,
+    pub order: Option<CrawlingOrder>,
+    /// The levels of the players to crawl. If not set, the levels of a
+    /// resumed backup are kept
+    pub min_level: Option<u32>,
+    pub max_level: Option<u32>,
+    /// Continues unfinished crawls from the backups in `output`. Servers with
+    /// a complete backup are skipped
+    pub resume: bool,
+    /// The directory the backups are written to
+    pub output: PathBuf,
+}
+
+impl Default for CrawlJob {
+    fn default() -> Self {
+        Self {
+            name: "crawl".to_string(),
+            cron: "0 3 * * *".to_string(),
+            urls: vec![],
+            file: None,
+            include: vec![],
+            exclude: vec![],
+            concurrency: 4,
+            threads: 1,
+            max_runtime_minutes: None,
+            order: None,
+            min_level: None,
+            max_level: None,
+            resume: false,
+            output: PathBuf::from("."),
+        }
+    }
+}
+
+/// Creates a new random password for the crawler accounts we register
+pub fn generate_crawler_password() -> String {
+    let mut rng = fastrand::Rng::new();
+    (0..24).map(|_| rng.alphanumeric()).collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CrawlerConfig {
+    /// The password of the crawler accounts, that we register ourselves
+    pub password: String,
+    /// Also tries the reversed name as the password, which is what crawlers
+    /// registered by older versions use
+    pub legacy_password: bool,
+    /// The race of newly registered crawlers. Random, if this is not set
+    pub race: Option<Race>,
+    pub class: Option<Class>,
+    pub gender: Option<Gender>,
+    /// Existing accounts to crawl with, keyed by the server url. No crawler
+    /// is registered on these servers
+    pub logins: HashMap<String, CrawlerCredentials>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CrawlerCredentials {
+    pub name: String,
+    pub password: String,
+}
+
+impl CrawlerConfig {
+    /// Some of the crawler passwords are encrypted and can not be used,
+    /// until the config has been unlocked
+    pub fn has_encrypted_passwords(&self) -> bool {
+        vault::is_encrypted(&self.password)
+            || self
+                .logins
+                .values()
+                .any(|a| vault::is_encrypted(&a.password))
+    }
+
+    /// The generated crawler password & the passwords of all crawler logins
+    fn passwords_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        std::iter::once(&mut self.password)
+            .chain(self.logins.values_mut().map(|a| &mut a.password))
+    }
+
+    /// The account the crawler on this server uses
+    pub fn login(&self, base_name: &str, server: &ServerIdent) -> CrawlerLogin {
+        let existing = self
+            .logins
+            .iter()
+            .find(|(url, _)| ServerIdent::new(url).id == server.id);
+        if let Some((_, creds)) = existing {
+            return CrawlerLogin {
+                name: creds.name.clone(),
+                password: creds.password.clone(),
+                legacy_password: None,
+                register: None,
+            };
+        }
+        CrawlerLogin {
+            name: base_name.to_string(),
+            password: self.password.clone(),
+            legacy_password: self
+                .legacy_password
+                .then(|| base_name.chars().rev().collect()),
+            register: Some(CrawlerRegistration {
+                race: self.race,
+                class: self.class,
+                gender: self.gender,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations() {
+        // The config, what migrate should return & if the crawlers have to
+        // try the legacy password afterwards
+        let cases: &[(&str, Result<u32, &str>, Option<bool>)] = &[
+            ("", Ok(0), Some(true)),
+            ("[crawler]\npassword = \"a\"", Ok(0), None),
+            ("version = 1", Ok(1), None),
+            ("version = 99", Err("too new"), None),
+            ("version = -1", Err("invalid"), None),
+            ("version = \"1\"", Err("invalid"), None),
+        ];
+        for (input, expected, legacy) in cases {
+            let mut table: toml::Table = toml::from_str(input).unwrap();
+            let res = migrate(&mut table);
+            match (expected, res) {
+                (Ok(version), Ok(res)) => {
+                    assert_eq!(*version, res, "{input}");
+                    assert_eq!(
+                        table.get("version").and_then(|a| a.as_integer()),
+                        Some(i64::from(CONFIG_VERSION)),
+                        "{input}"
+                    );
+                    let res_legacy = table
+                        .get("crawler")
+                        .and_then(|a| a.get("legacy_password"))
+                        .and_then(|a| a.as_bool());
+                    assert_eq!(*legacy, res_legacy, "{input}");
+                }
+                (Err("too new"), Err(ConfigError::TooNew(99))) => {}
+                (Err("invalid"), Err(ConfigError::Invalid(_))) => {}
+                (expected, res) => {
+                    panic!("{input}: expected {expected:?}, got {res:?}")
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn validation() {
+        fn job(name: &str) -> CrawlJob {
+            CrawlJob {
+                name: name.to_string(),
+                ..Default::default()
+            }
+        }
+        type Change = fn(&mut Config);
+        // A change to a valid config & the problem it should cause
+        let cases: Vec<(Change, Option<&str>)> = vec![
+            (|_| {}, None),
+            (
+                |c| c.base_name = " ".to_string(),
+                Some("base_name is empty"),
+            ),
+            (
+                |c| c.start_threads = c.max_threads + 1,
+                Some("start_threads is above max_threads"),
+            ),
+            (
+                |c| {
+                    c.accounts.push(AccountConfig::Regular {
+                        name: "player".to_string(),
+                        pw_hash: PWHash::new("pw"),
+                        server: String::new(),
+                        config: CharacterConfig::default(),
+                    })
+                },
+                Some("player has an invalid server"),
+            ),
+            (
+                |c| c.crawl_exclude.push("re:(".to_string()),
+                Some("Invalid server pattern"),
+            ),
+            (
+                |c| c.crawl_jobs.extend([job("a"), job("a")]),
+                Some("The job a exists twice"),
+            ),
+            (
+                |c| {
+                    c.crawl_jobs.push(CrawlJob {
+                        cron: "not a cron".to_string(),
+                        ..Default::default()
+                    })
+                },
+                Some("Invalid job crawl"),
+            ),
+            (
+                |c| {
+                    c.crawl_jobs.push(CrawlJob {
+                        min_level: Some(200),
+                        max_level: Some(100),
+                        ..Default::default()
+                    })
+                },
+                Some("min_level above its max_level"),
+            ),
+        ];
+        for (pos, (change, expected)) in cases.into_iter().enumerate() {
+            let mut config = Config::default();
+            change(&mut config);
+            let problems = config.validate();
+            match expected {
+                None => assert!(problems.is_empty(), "{pos}: {problems:?}"),
+                Some(expected) => assert!(
+                    problems.len() == 1 && problems[0].contains(expected),
+                    "{pos}: {problems:?}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn mushroom_budget() {
+        let policy = MushroomPolicy {
+            max_per_day: 3,
+            min_missing: 2,
+            reserve: 10,
+        };
+        // Spent today, mushrooms left, missing items of the target
+        let cases = [
+            (0, 50, 2, true),
+            (2, 50, 2, true),
+            // At & over the daily limit
+            (3, 50, 2, false),
+            (4, 50, 2, false),
+            // At & under the reserve
+            (0, 11, 2, true),
+            (0, 10, 2, false),
+            (0, 5, 2, false),
+            // Not enough new items on the target
+            (0, 50, 1, false),
+            (0, 50, 5, true),
+        ];
+        for (spent, mushrooms, missing, expected) in cases {
+            assert_eq!(
+                policy.should_spend(spent, mushrooms, missing),
+                expected,
+                "{spent} {mushrooms} {missing}"
+            );
+        }
+
+        let never = MushroomPolicy {
+            max_per_day: 0,
+            ..policy
+        };
+        assert!(!never.should_spend(0, 50, 10));
+        let any_target = MushroomPolicy {
+            min_missing: 0,
+            ..policy
+        };
+        assert!(any_target.should_spend(0, 50, 0));
+    }
+
+    #[test]
+    fn battle_stop_reasons() {
+        let unlimited = BattleLimits::default();
+        assert_eq!(unlimited.stop_reason(100, 10_000), None);
+
+        let limits = BattleLimits {
+            max_consecutive_losses: 3,
+            target_items: 500,
+            ..Default::default()
+        };
+        assert_eq!(limits.stop_reason(0, 0), None);
+        assert_eq!(limits.stop_reason(2, 499), None);
+        assert_eq!(
+            limits.stop_reason(3, 0).as_deref(),
+            Some("Lost 3 fights in a row")
+        );
+        assert_eq!(
+            limits.stop_reason(4, 0).as_deref(),
+            Some("Lost 4 fights in a row")
+        );
+        assert_eq!(
+            limits.stop_reason(0, 500).as_deref(),
+            Some("Reached 500 items")
+        );
+        assert_eq!(
+            limits.stop_reason(0, 600).as_deref(),
+            Some("Reached 500 items")
+        );
+        // The losses are reported first, if both limits are reached
+        assert_eq!(
+            limits.stop_reason(3, 500).as_deref(),
+            Some("Lost 3 fights in a row")
+        );
+    }
+
+    #[test]
+    fn restore_does_not_write() {
+        let dir = std::env::temp_dir()
+            .join(format!("sf-helper-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("helper.toml");
+
+        // A config from before versioning & the crawler settings existed
+        let mut table = toml::Table::try_from(Config::default()).unwrap();
+        table.remove("version");
+        table.remove("crawler");
+        let old = toml::to_string(&table).unwrap();
+        std::fs::write(&path, &old).unwrap();
+
+        let config = Config::restore(&path).unwrap();
+        assert_eq!(config.migrated_from, Some(0));
+        assert!(config.unsaved_password);
+        assert!(!config.crawler.password.is_empty());
+        assert!(config.crawler.legacy_password);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), old);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        config.write().unwrap();
+        let written = Config::restore(&path).unwrap();
+        assert_eq!(written.crawler.password, config.crawler.password);
+        assert_eq!(written.migrated_from, None);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("helper.toml.v0.bak")).unwrap(),
+            old
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -27,6 +27,9 @@ impl Crawler {
             // guard
             let mut lock = self.que.lock().unwrap();
             loop {
+                if lock.paused {
+                    break CrawlAction::Wait;
+                }
                 match lock.todo_accounts.pop() {
                     Some(entry) => {
                         if entry.chars().all(|a| a.is_ascii_digit()) {
@@ -109,7 +112,9 @@ impl Crawler {
                     }
                 }
                 lock.in_flight_pages.retain(|a| a != page);
-                Message::PageCrawled
+                Message::PageCrawled {
+                    server: self.server_id,
+                }
             }
             CrawlAction::Character(name, que_id) => {
                 let cmd = Command::ViewPlayer {
@@ -203,27 +208,116 @@ impl Crawler {
     }
 }
 
+/// The account a crawler logs in with
+#[derive(Debug, Clone)]
+pub struct CrawlerLogin {
+    pub name: String,
+    pub password: String,
+    /// The password crawlers used to have (the reversed name). This is only
+    /// tried, if the login with the actual password fails and is replaced
+    /// with the actual password, once that worked
+    pub legacy_password: Option<String>,
+    /// How to register the account, if it does not exist yet. None, if the
+    /// account was given by the user and must not be registered
+    pub register: Option<CrawlerRegistration>,
+}
+
+/// The look of newly registered crawlers. Everything, that is None is chosen
+/// randomly
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CrawlerRegistration {
+    pub race: Option<Race>,
+    pub class: Option<Class>,
+    pub gender: Option<Gender>,
+}
+
 #[derive(Debug)]
 pub struct CrawlerState {
     pub session: RwLock<Session>,
     pub gs: Mutex<GameState>,
 }
 impl CrawlerState {
+    /// Replaces the old password of a crawler (the reversed name), which
+    /// anyone could guess, with the configured one. If that does not work,
+    /// the crawler keeps using the session of the old password
+    async fn change_legacy_password(
+        mut session: Session,
+        resp: Response,
+        server: ServerConnection,
+        name: &str,
+        old: &str,
+        new: &str,
+    ) -> Result<Self, SFError> {
+        let url = session.server_url().to_string();
+        let cmd = sf_api::command::Command::ChangePassword {
+            username: name.to_string(),
+            old: old.to_string(),
+            new: new.to_string(),
+        };
+        let mut gs = GameState::new(resp)?;
+        match session.send_command(&cmd).await {
+            Ok(_) => {
+                info!("Changed the password of the crawler {name} on {url}");
+                // Changing the password can invalidate the session
+                let mut new_session = Session::new(name, new, server);
+                let resp = new_session.login().await?;
+                gs = GameState::new(resp)?;
+                session = new_session;
+            }
+            Err(e) => {
+                log::warn!(
+                    "Could not change the old password of the crawler {name} \
+                     on {url}: {e}"
+                );
+            }
+        }
+        sleep(Duration::from_secs(3)).await;
+        Ok(Self {
+            session: RwLock::new(session),
+            gs: Mutex::new(gs),
+        })
+    }
+
     pub async fn try_login(
-        name: String,
+        login: CrawlerLogin,
         server: ServerConnection,
     ) -> Result<Self, SFError> {
-        let password = name.chars().rev().collect::<String>();
-        let mut session = Session::new(&name, &password, server.clone());
-        debug!("Logging in {name} on {}", session.server_url());
-        if let Ok(resp) = session.login().await {
-            debug!("Successfully logged in {name} on {}", session.server_url());
-            let gs = GameState::new(resp)?;
-            sleep(Duration::from_secs(3)).await;
-            return Ok(Self {
-                session: RwLock::new(session),
-                gs: Mutex::new(gs),
-            });
+        let CrawlerLogin {
+            name,
+            password,
+            legacy_password,
+            register,
+        } = login;
+        let passwords = std::iter::once(&password).chain(&legacy_password);
+        let mut last_err = None;
+        let mut url = String::new();
+        for pw in passwords {
+            let mut session = Session::new(&name, pw, server.clone());
+            url = session.server_url().to_string();
+            debug!("Logging in {name} on {url}");
+            match session.login().await {
+                Ok(resp) => {
+                    debug!("Successfully logged in {name} on {url}");
+                    if *pw != password {
+                        return Self::change_legacy_password(
+                            session, resp, server, &name, pw, &password,
+                        )
+                        .await;
+                    }
+                    let gs = GameState::new(resp)?;
+                    sleep(Duration::from_secs(3)).await;
+                    return Ok(Self {
+                        session: RwLock::new(session),
+                        gs: Mutex::new(gs),
+                    });
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        let Some(register) = register else {
+            return Err(last_err.unwrap_or(SFError::InvalidRequest(
+                "Could not login the crawler",
+            )));
         };
 
         let all_races = [
@@ -251,13 +345,16 @@ impl CrawlerState {
         ];
 
         let mut rng = fastrand::Rng::new();
-        let gender = rng.choice([Gender::Female, Gender::Male]).unwrap();
-        let race = rng.choice(all_races).unwrap();
-        let class = rng.choice(all_classes).unwrap();
-        debug!(
-            "Registering new crawler account {name} on {}",
-            session.server_url()
-        );
+        let gender = register.gender.unwrap_or_else(|| {
+            rng.choice([Gender::Female, Gender::Male]).unwrap()
+        });
+        let race = register
+            .race
+            .unwrap_or_else(|| rng.choice(all_races).unwrap());
+        let class = register
+            .class
+            .unwrap_or_else(|| rng.choice(all_classes).unwrap());
+        debug!("Registering new crawler account {name} on {url}");
 
         let (session, resp) = Session::register(
             &name,
@@ -303,7 +400,15 @@ impl std::fmt::Display for CrawlAction {
 }
 
 #[derive(
-    Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq,
+    Debug,
+    Serialize,
+    Deserialize,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
 )]
 pub enum CrawlingOrder {
     #[default]
@@ -350,9 +455,30 @@ pub struct WorkerQue {
     pub min_level: u32,
     pub max_level: u32,
     pub self_init: bool,
+    /// No new actions are handed out, because we are shutting down
+    pub paused: bool,
 }
 
 impl WorkerQue {
+    /// Changes the levels of the players, that should be crawled. Players,
+    /// that were skipped before, but are in the new range, are crawled again
+    pub fn set_level_range(&mut self, min: u32, max: u32) {
+        self.min_level = min.max(1);
+        self.max_level = max.max(self.min_level).min(9999);
+
+        let in_range: Vec<u32> = self
+            .lvl_skipped_accounts
+            .range(self.min_level..=self.max_level)
+            .map(|(lvl, _)| *lvl)
+            .collect();
+        for lvl in in_range {
+            let Some(mut todo) = self.lvl_skipped_accounts.remove(&lvl) else {
+                continue;
+            };
+            self.todo_accounts.append(&mut todo);
+        }
+    }
+
     pub fn create_backup(
         &self,
         player_info: &IntMap<u32, CharacterInfo>,
@@ -397,6 +523,15 @@ pub enum CrawlerError {
 }
 
 impl CrawlerError {
+    /// A short, stable name of this kind of error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CrawlerError::Generic(_) => "generic",
+            CrawlerError::NotFound => "not_found",
+            CrawlerError::RateLimit => "rate_limit",
+        }
+    }
+
     #[allow(clippy::single_match)]
     pub fn from_err(value: SFError) -> Self {
         match &value {
@@ -0,0 +1,445 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDate};
+use log::warn;
+use nohash_hasher::{IntMap, IntSet};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistoryKind {
+    Fight,
+    Lure,
+}
+
+/// A single fight, or lure, that one of our characters has done
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub time: DateTime<Local>,
+    pub kind: HistoryKind,
+    pub opponent: String,
+    pub opponent_uid: u32,
+    pub won: bool,
+    /// The amount of new scrapbook items this fight has given us
+    #[serde(default)]
+    pub items: usize,
+    #[serde(default)]
+    pub mushrooms: u32,
+    /// The silver gained from this lure
+    #[serde(default)]
+    pub silver: u64,
+    /// The souls gained from this lure
+    #[serde(default)]
+    pub souls: u64,
+    /// The level of the opponent at the time of the fight. 0 if unknown
+    #[serde(default)]
+    pub opponent_level: u16,
+    /// The amount of items the opponent had equiped at the time of the fight
+    #[serde(default)]
+    pub opponent_items: usize,
+}
+
+/// The durable history of all fights & lures of a character. Every entry is
+/// appended to a JSON Lines file in the history directory of the config, so
+/// that nothing is lost on restart
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<HistoryEntry>,
+    /// The results of all previous lures, grouped by the victim. This is
+    /// updated, whenever an entry is added, because it is needed on every
+    /// update of the lure targets
+    lures_by_victim: IntMap<u32, LureStats>,
+    /// The day of the last lure and the players lured on that day
+    lured_on: Option<(NaiveDate, IntSet<u32>)>,
+    lure_yield: LureYield,
+}
+
+impl History {
+    /// Reads the history of the character from `dir`
+    pub fn load(dir: &Path, server_ident: &str, name: &str) -> History {
+        let file_name =
+            format!("{server_ident}_{}.history", name.to_lowercase());
+        let path = dir.join(&file_name);
+        // Older versions wrote the history to the working directory
+        if !path.exists() && Path::new(&file_name).exists() {
+            let moved = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::rename(&file_name, &path));
+            if let Err(e) = moved {
+                warn!("Could not move {file_name} to {}: {e}", path.display());
+            }
+        }
+        let mut entries = vec![];
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                for line in content.lines().filter(|a| !a.trim().is_empty()) {
+                    match serde_json::from_str(line) {
+                        Ok(entry) => entries.push(entry),
+                        Err(e) => warn!(
+                            "Invalid history entry in {}: {e}",
+                            path.display()
+                        ),
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not read history {}: {e}", path.display()),
+        }
+        let mut history = History {
+            path: Some(path),
+            ..Default::default()
+        };
+        for entry in entries {
+            history.add(entry);
+        }
+        history
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if let Some(path) = &self.path {
+            if let Err(e) = append_line(path, &entry) {
+                warn!("Could not write history {}: {e}", path.display());
+            }
+        }
+        self.add(entry);
+    }
+
+    fn add(&mut self, entry: HistoryEntry) {
+        if entry.kind == HistoryKind::Lure {
+            self.lure_yield.add(&entry);
+            self.lures_by_victim
+                .entry(entry.opponent_uid)
+                .or_default()
+                .add(&entry);
+            let day = entry.time.date_naive();
+            match &mut self.lured_on {
+                Some((lure_day, lured)) if *lure_day == day => {
+                    lured.insert(entry.opponent_uid);
+                }
+                Some((lure_day, _)) if *lure_day > day => {}
+                _ => {
+                    let lured = std::iter::once(entry.opponent_uid).collect();
+                    self.lured_on = Some((day, lured));
+                }
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// The amount of mushrooms spent on fights & lures today
+    pub fn mushrooms_spent_today(&self) -> u32 {
+        self.mushrooms_spent_on(Local::now().date_naive())
+    }
+
+    /// The amount of mushrooms spent on fights & lures on that day
+    pub fn mushrooms_spent_on(&self, day: NaiveDate) -> u32 {
+        self.entries
+            .iter()
+            .rev()
+            .skip_while(|a| a.time.date_naive() > day)
+            .take_while(|a| a.time.date_naive() == day)
+            .map(|a| a.mushrooms)
+            .sum()
+    }
+
+    /// Checks if the player has already been lured today
+    pub fn lured_today(&self, uid: u32) -> bool {
+        self.lured_on.as_ref().is_some_and(|(day, lured)| {
+            *day == Local::now().date_naive() && lured.contains(&uid)
+        })
+    }
+
+    /// The combined results of all lures, that match the filter
+    pub fn lure_stats(&self, filter: &HistoryFilter) -> LureStats {
+        let mut res = LureStats::default();
+        for entry in self
+            .filtered(filter)
+            .filter(|a| a.kind == HistoryKind::Lure)
+        {
+            res.add(entry);
+        }
+        res
+    }
+
+    /// The results of all previous lures against this player
+    pub fn lures_against(&self, uid: u32) -> Option<LureStats> {
+        self.lures_by_victim.get(&uid).copied()
+    }
+
+    /// How much the won lures of this character have paid out so far
+    pub fn lure_yield(&self) -> &LureYield {
+        &self.lure_yield
+    }
+
+    /// The file, that this history would be exported to. This is next to the
+    /// history file, so None, if the history has not been loaded
+    pub fn export_path(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|a| a.with_extension("csv"))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut res = String::from(
+            "time,kind,opponent,opponent_uid,opponent_level,opponent_items,\
+             won,items,mushrooms,silver,souls\n",
+        );
+        for entry in &self.entries {
+            _ = writeln!(
+                &mut res,
+                "{},{:?},\"{}\",{},{},{},{},{},{},{},{}",
+                entry.time.to_rfc3339(),
+                entry.kind,
+                entry.opponent.replace('"', "\"\""),
+                entry.opponent_uid,
+                entry.opponent_level,
+                entry.opponent_items,
+                entry.won,
+                entry.items,
+                entry.mushrooms,
+                entry.silver,
+                entry.souls,
+            );
+        }
+        res
+    }
+
+    pub fn filtered<'a>(
+        &'a self,
+        filter: &'a HistoryFilter,
+    ) -> impl DoubleEndedIterator<Item = &'a HistoryEntry> + 'a {
+        self.entries.iter().filter(|a| filter.matches(a))
+    }
+
+    pub fn daily(
+        &self,
+        filter: &HistoryFilter,
+    ) -> BTreeMap<NaiveDate, DailySummary> {
+        let mut res: BTreeMap<NaiveDate, DailySummary> = BTreeMap::new();
+        for entry in self.filtered(filter) {
+            let day = res.entry(entry.time.date_naive()).or_default();
+            match (entry.kind, entry.won) {
+                (HistoryKind::Fight, true) => day.fights_won += 1,
+                (HistoryKind::Fight, false) => day.fights_lost += 1,
+                (HistoryKind::Lure, true) => day.lures_won += 1,
+                (HistoryKind::Lure, false) => day.lures_lost += 1,
+            }
+            day.items += entry.items;
+            day.mushrooms += entry.mushrooms;
+        }
+        res
+    }
+}
+
+fn append_line(path: &Path, entry: &HistoryEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DailySummary {
+    pub fights_won: usize,
+    pub fights_lost: usize,
+    pub lures_won: usize,
+    pub lures_lost: usize,
+    pub items: usize,
+    pub mushrooms: u32,
+}
+
+/// The summed up outcome of a set of lures
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LureStats {
+    pub won: usize,
+    pub lost: usize,
+    pub silver: u64,
+    pub souls: u64,
+}
+
+impl LureStats {
+    fn add(&mut self, entry: &HistoryEntry) {
+        match entry.won {
+            true => self.won += 1,
+            false => self.lost += 1,
+        }
+        self.silver += entry.silver;
+        self.souls += entry.souls;
+    }
+
+    pub fn total(&self) -> usize {
+        self.won + self.lost
+    }
+
+    /// The share of lures (0-1), that were won
+    pub fn win_rate(&self) -> f32 {
+        self.won as f32 / self.total().max(1) as f32
+    }
+}
+
+/// The silver & souls, that won lures have given us, relative to the level of
+/// the victim
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LureYield {
+    won: u64,
+    levels: u64,
+    silver: u64,
+    souls: u64,
+}
+
+impl LureYield {
+    fn add(&mut self, entry: &HistoryEntry) {
+        // Lures from before the level was recorded can not tell us anything
+        // about how the reward scales
+        if !entry.won || entry.opponent_level == 0 {
+            return;
+        }
+        self.won += 1;
+        self.levels += u64::from(entry.opponent_level);
+        self.silver += entry.silver;
+        self.souls += entry.souls;
+    }
+
+    /// The average silver & souls of a won lure. None, if we have not won a
+    /// lure yet
+    pub fn average(&self) -> Option<(f32, f32)> {
+        (self.won > 0).then(|| {
+            (
+                self.silver as f32 / self.won as f32,
+                self.souls as f32 / self.won as f32,
+            )
+        })
+    }
+
+    /// The silver & souls we got per level of the victim. None, if we have not
+    /// won a lure yet
+    pub fn per_level(&self) -> Option<(f32, f32)> {
+        (self.levels > 0).then(|| {
+            (
+                self.silver as f32 / self.levels as f32,
+                self.souls as f32 / self.levels as f32,
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistoryFilter {
+    pub kind: KindFilter,
+    pub result: ResultFilter,
+    pub opponent: String,
+    /// Only show entries from the last n days. 0 shows everything
+    pub days: u32,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        let kind_ok = match self.kind {
+            KindFilter::All => true,
+            KindFilter::Fights => entry.kind == HistoryKind::Fight,
+            KindFilter::Lures => entry.kind == HistoryKind::Lure,
+        };
+        let result_ok = match self.result {
+            ResultFilter::All => true,
+            ResultFilter::Won => entry.won,
+            ResultFilter::Lost => !entry.won,
+        };
+        let opponent = self.opponent.trim().to_lowercase();
+        let opponent_ok = opponent.is_empty()
+            || entry.opponent.to_lowercase().contains(&opponent);
+        let days_ok = self.days == 0
+            || entry.time.date_naive()
+                > Local::now().date_naive()
+                    - chrono::Duration::days(self.days.into());
+        kind_ok && result_ok && opponent_ok && days_ok
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KindFilter {
+    #[default]
+    All,
+    Fights,
+    Lures,
+}
+
+#[allow(clippy::to_string_trait_impl)]
+impl ToString for KindFilter {
+    fn to_string(&self) -> String {
+        match self {
+            KindFilter::All => "All",
+            KindFilter::Fights => "Fights",
+            KindFilter::Lures => "Lures",
+        }
+        .to_string()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFilter {
+    #[default]
+    All,
+    Won,
+    Lost,
+}
+
+#[allow(clippy::to_string_trait_impl)]
+impl ToString for ResultFilter {
+    fn to_string(&self) -> String {
+        match self {
+            ResultFilter::All => "All",
+            ResultFilter::Won => "Won",
+            ResultFilter::Lost => "Lost",
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeZone};
+
+    use super::*;
+
+    fn entry(time: &str, mushrooms: u32) -> HistoryEntry {
+        let time =
+            NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        HistoryEntry {
+            time: Local.from_local_datetime(&time).unwrap(),
+            kind: HistoryKind::Fight,
+            opponent: "opponent".to_string(),
+            opponent_uid: 1,
+            won: true,
+            items: 1,
+            mushrooms,
+            silver: 0,
+            souls: 0,
+            opponent_level: 0,
+            opponent_items: 0,
+        }
+    }
+
+    #[test]
+    fn mushrooms_per_day() {
+        let mut history = History::default();
+        history.push(entry("2024-01-01 23:00", 1));
+        history.push(entry("2024-01-02 08:00", 1));
+        history.push(entry("2024-01-02 09:00", 0));
+        history.push(entry("2024-01-02 12:00", 1));
+        history.push(entry("2024-01-03 08:00", 1));
+
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        assert_eq!(history.mushrooms_spent_on(day(1)), 1);
+        assert_eq!(history.mushrooms_spent_on(day(2)), 2);
+        assert_eq!(history.mushrooms_spent_on(day(3)), 1);
+        assert_eq!(history.mushrooms_spent_on(day(4)), 0);
+    }
+}
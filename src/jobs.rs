@@ -0,0 +1,298 @@
+use std::{collections::VecDeque, time::Duration};
+
+use chrono::{Local, NaiveDateTime};
+use iced::Command;
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use tokio::time::sleep;
+
+use crate::{
+    config::CrawlJob,
+    message::Message,
+    progress::{self, CrawlEvent, ProgressFormat, ServerResult},
+    schedule::CronSpec,
+    select::ServerSelect,
+    server::CrawlingStatus,
+    CLICrawling, CrawlOptions, Helper,
+};
+
+/// A crawl, that is repeated whenever its cron spec matches
+#[derive(Debug, Clone)]
+pub struct ScheduledJob {
+    pub name: String,
+    pub cron: CronSpec,
+    pub servers: ServerSelect,
+    pub concurrency: usize,
+    pub threads: usize,
+    pub options: CrawlOptions,
+}
+
+impl ScheduledJob {
+    pub fn from_config(job: &CrawlJob) -> Result<Self, String> {
+        let cron = job
+            .cron
+            .parse()
+            .map_err(|e| format!("Invalid job {}: {e}", job.name))?;
+        Ok(ScheduledJob {
+            name: job.name.clone(),
+            cron,
+            servers: ServerSelect::from_job(job)?,
+            concurrency: job.concurrency.clamp(1, 50),
+            threads: job.threads.clamp(1, 50),
+            options: CrawlOptions {
+                order: job.order,
+                min_level: job.min_level,
+                max_level: job.max_level,
+                resume: job.resume,
+                output: job.output.clone(),
+                time_budget: job.max_runtime_minutes,
+                progress: ProgressFormat::default(),
+            },
+        })
+    }
+}
+
+/// Keeps the process alive and starts the jobs one after another. A job,
+/// that becomes due while another one is running, waits for it to finish
+#[derive(Debug)]
+pub struct CrawlScheduler {
+    jobs: Vec<ScheduledJob>,
+    queue: VecDeque<usize>,
+    current: Option<usize>,
+    runs: u64,
+    /// Where messages between runs are written to
+    mbp: MultiProgress,
+}
+
+impl CrawlScheduler {
+    /// Creates the scheduler and the commands, that wait for the first run
+    /// of each job
+    pub fn new(
+        jobs: Vec<ScheduledJob>,
+    ) -> Result<(Self, Command<Message>), String> {
+        if jobs.is_empty() {
+            return Err("There are no crawl jobs to schedule".to_string());
+        }
+        let scheduler = CrawlScheduler {
+            jobs,
+            queue: VecDeque::new(),
+            current: None,
+            runs: 0,
+            mbp: MultiProgress::new(),
+        };
+        let mut commands = vec![];
+        for idx in 0..scheduler.jobs.len() {
+            commands.push(scheduler.wait_for_next(idx)?);
+        }
+        Ok((scheduler, Command::batch(commands)))
+    }
+
+    fn wait_for_next(&self, idx: usize) -> Result<Command<Message>, String> {
+        let job = &self.jobs[idx];
+        let now = Local::now().naive_local();
+        let Some(next) = job.cron.next_after(now) else {
+            return Err(format!("The job {} will never run", job.name));
+        };
+        _ = self.mbp.println(format!(
+            "Next run of {} at {}",
+            job.name,
+            next.format("%Y-%m-%d %H:%M")
+        ));
+        Ok(Command::perform(sleep(time_until(now, next)), move |_| {
+            Message::CrawlJobDue(idx)
+        }))
+    }
+}
+
+fn time_until(now: NaiveDateTime, then: NaiveDateTime) -> Duration {
+    (then - now).to_std().unwrap_or_default()
+}
+
+impl Helper {
+    /// Starts crawling the selected servers in the CLI. The run ends, once
+    /// all of them are done, or the time budget is used up
+    pub fn start_cli_run(
+        &mut self,
+        run: u64,
+        servers: ServerSelect,
+        concurrency: usize,
+        threads: usize,
+        options: CrawlOptions,
+    ) -> Command<Message> {
+        let mut commands = vec![];
+        if let Some(minutes) = options.time_budget {
+            commands.push(Command::perform(
+                sleep(Duration::from_secs(minutes * 60)),
+                move |_| Message::CLICrawlTimeout(run),
+            ));
+        }
+        let mbp = match options.progress {
+            ProgressFormat::Bars => MultiProgress::new(),
+            ProgressFormat::Json => {
+                MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+            }
+        };
+        self.cli_crawling = Some(CLICrawling {
+            todo_servers: Vec::new(),
+            mbp,
+            active: concurrency,
+            threads,
+            options,
+            timed_out: false,
+            run,
+            interrupted: false,
+            servers: Default::default(),
+            summary: Default::default(),
+        });
+
+        let default_exclude = self.config.crawl_exclude.clone();
+        commands.push(Command::perform(
+            servers.resolve(default_exclude),
+            move |servers| Message::CrawlServersRes {
+                servers,
+                concurrency,
+            },
+        ));
+        Command::batch(commands)
+    }
+
+    /// Stops crawling all servers of the current CLI run and writes their
+    /// backups, so that they can be resumed later
+    pub fn stop_cli_run(&mut self) -> Command<Message> {
+        let Some(cli) = &mut self.cli_crawling else {
+            return Command::none();
+        };
+        cli.todo_servers.clear();
+
+        let mut res = vec![];
+        let ids: Vec<_> = self
+            .servers
+            .0
+            .iter()
+            .filter(|a| a.1.headless_progress.is_some())
+            .map(|a| *a.0)
+            .collect();
+        for id in ids {
+            let Some(server) = self.servers.0.remove(&id) else {
+                continue;
+            };
+            let Some(pb) = server.headless_progress else {
+                continue;
+            };
+            let backup = match &server.crawling {
+                CrawlingStatus::Crawling {
+                    que, player_info, ..
+                } => Some(que.lock().unwrap().create_backup(player_info)),
+                _ => None,
+            };
+            let path = cli.options.backup_path(&server.ident.ident);
+            let progress = cli.server_done(id, ServerResult::Partial);
+            let format = cli.options.progress;
+            let ident = server.ident.ident;
+            res.push(Command::perform(
+                async move {
+                    if let Some(backup) = backup {
+                        match backup.write_path(&path).await {
+                            Ok(()) => progress::emit(
+                                format,
+                                &CrawlEvent::BackupWritten {
+                                    server: &ident,
+                                    path: &path,
+                                },
+                            ),
+                            Err(e) => {
+                                pb.println(e.to_string());
+                                progress::emit(
+                                    format,
+                                    &CrawlEvent::Error {
+                                        server: &ident,
+                                        kind: "backup",
+                                        error: &e.to_string(),
+                                    },
+                                );
+                            }
+                        }
+                    }
+                    progress::emit(
+                        format,
+                        &CrawlEvent::finished(
+                            &ident,
+                            ServerResult::Partial,
+                            &progress,
+                        ),
+                    );
+                    pb.finish_and_clear();
+                },
+                |_| Message::NextCLICrawling,
+            ));
+        }
+        Command::batch(res)
+    }
+
+    /// Ends the current CLI run. Without a scheduler, this exits the process
+    pub fn finish_cli_run(&mut self, msg: &str) -> Command<Message> {
+        let Some(cli) = self.cli_crawling.take() else {
+            return Command::none();
+        };
+        _ = cli.mbp.println(msg);
+        let code = cli.summary.exit_code();
+        cli.emit(&CrawlEvent::Summary {
+            summary: &cli.summary,
+            timed_out: cli.timed_out,
+            interrupted: cli.interrupted,
+            exit_code: code,
+        });
+        let Some(scheduler) = &mut self.crawl_scheduler else {
+            std::process::exit(code);
+        };
+        if self.shutting_down {
+            std::process::exit(code);
+        }
+        scheduler.current = None;
+        self.start_next_job()
+    }
+
+    pub fn crawl_job_due(&mut self, idx: usize) -> Command<Message> {
+        let Some(scheduler) = &mut self.crawl_scheduler else {
+            return Command::none();
+        };
+        let Some(job) = scheduler.jobs.get(idx) else {
+            return Command::none();
+        };
+        if scheduler.current == Some(idx) || scheduler.queue.contains(&idx) {
+            _ = scheduler.mbp.println(format!(
+                "Skipping {}, because the last run is not done yet",
+                job.name
+            ));
+        } else {
+            scheduler.queue.push_back(idx);
+        }
+        let next = match scheduler.wait_for_next(idx) {
+            Ok(next) => next,
+            Err(e) => {
+                _ = scheduler.mbp.println(e);
+                Command::none()
+            }
+        };
+        if self.cli_crawling.is_some() {
+            return next;
+        }
+        Command::batch([next, self.start_next_job()])
+    }
+
+    fn start_next_job(&mut self) -> Command<Message> {
+        let Some(scheduler) = &mut self.crawl_scheduler else {
+            return Command::none();
+        };
+        let Some(idx) = scheduler.queue.pop_front() else {
+            return Command::none();
+        };
+        let job = scheduler.jobs[idx].clone();
+        scheduler.current = Some(idx);
+        scheduler.runs += 1;
+        let run = scheduler.runs;
+        _ = scheduler.mbp.println(format!("Starting {}", job.name));
+        self.start_cli_run(
+            run, job.servers, job.concurrency, job.threads, job.options,
+        )
+    }
+}
@@ -67,7 +67,7 @@ impl LoginState {
         &self,
         accounts: &[AccountConfig],
         has_active: bool,
-    ) -> Element<Message> {
+    ) -> Element<'_, Message> {
         let login_type_button = |label, filter, current_filter| {
             let label: widget::text::Text<'_, Theme, Renderer> = text(label);
             let button = button(label).style(if filter == current_filter {
@@ -427,6 +427,37 @@ impl SSOValidator {
 }
 
 impl Helper {
+    /// Logs in all characters, that are set to login on startup
+    pub fn auto_login(&mut self) -> Command<Message> {
+        let mut commands = vec![];
+        let mut loading = 0;
+
+        for acc in &self.config.accounts {
+            if !acc.has_auto_login() {
+                continue;
+            }
+            let acc = acc.clone();
+            loading += 1;
+            commands.push(Command::perform(
+                async move {
+                    sleep(Duration::from_millis((loading - 1) * 200)).await
+                },
+                move |_| Message::Login {
+                    account: acc,
+                    auto_login: true,
+                },
+            ));
+        }
+
+        if loading > 0 {
+            self.current_view = View::Overview {
+                selected: Default::default(),
+                action: Default::default(),
+            };
+        }
+        Command::batch(commands)
+    }
+
     pub fn login_regular(
         &mut self,
         name: String,
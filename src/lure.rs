@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::{
+    history::{History, LureStats, LureYield},
+    CharacterInfo,
+};
+
+/// How many levels a single piece of equipment is roughly worth in a fight
+/// against our underworld units. This is a guess, not a value from the game
+const ITEM_LEVEL_VALUE: f32 = 5.0;
+/// How quickly the chance to win changes with the level difference. Higher
+/// values make the estimate less confident. This is a guess, not a value from
+/// the game
+const LEVEL_SPREAD: f32 = 10.0;
+
+/// A player, that we could lure, together with our guess of how this would
+/// turn out
+#[derive(Debug, Clone)]
+pub struct LureCandidate {
+    pub info: CharacterInfo,
+    /// The estimated chance (0-1) of the lure being successful
+    pub success: f32,
+    /// The silver & souls we expect to get, if the lure is won. None, until
+    /// this character has won a lure
+    pub estimate: Option<(f32, f32)>,
+    /// The reward used for ranking. With lure results, this is the estimate
+    /// relative to an average won lure, so an average lure is worth 2 (1 for
+    /// silver, 1 for souls). Without them, this is the level of the victim
+    pub reward: f32,
+    /// The results of our previous lures against this player
+    pub previous: Option<LureStats>,
+}
+
+impl LureCandidate {
+    /// Estimates the outcome of a lure.
+    ///
+    /// The success chance starts as a logistic curve over the difference
+    /// between the average level of our units and the level of the victim,
+    /// where every equipped item counts as `ITEM_LEVEL_VALUE` levels. At an
+    /// even strength this is 50%, with `LEVEL_SPREAD` levels in our favor it
+    /// is about 73% and with twice that about 88%. The results of earlier
+    /// lures against the same player are then mixed in, with the estimate
+    /// counting as a single lure.
+    ///
+    /// The reward comes from the silver & souls of our won lures. Victims we
+    /// have won against before are expected to pay out the same again. For
+    /// everyone else, the reward scales with the level of the victim, like it
+    /// did in our previous lures
+    pub fn new(
+        info: &CharacterInfo,
+        unit_level: f32,
+        previous: Option<LureStats>,
+        lure_yield: &LureYield,
+    ) -> LureCandidate {
+        let strength =
+            info.level as f32 + info.equipment.len() as f32 * ITEM_LEVEL_VALUE;
+        let diff = unit_level - strength;
+        let mut success = 1.0 / (1.0 + (-diff / LEVEL_SPREAD).exp());
+        if let Some(previous) = previous {
+            success = (success + previous.won as f32)
+                / (1.0 + previous.total() as f32);
+        }
+
+        let estimate = match previous.filter(|a| a.won > 0) {
+            Some(previous) => Some((
+                previous.silver as f32 / previous.won as f32,
+                previous.souls as f32 / previous.won as f32,
+            )),
+            None => lure_yield.per_level().map(|(silver, souls)| {
+                (silver * info.level as f32, souls * info.level as f32)
+            }),
+        };
+        let reward = match (estimate, lure_yield.average()) {
+            (Some((silver, souls)), Some((avg_silver, avg_souls))) => {
+                silver / avg_silver.max(1.0) + souls / avg_souls.max(1.0)
+            }
+            _ => info.level as f32,
+        };
+        LureCandidate {
+            info: info.clone(),
+            success,
+            estimate,
+            reward,
+            previous,
+        }
+    }
+
+    /// The reward we expect to get on average, when we lure this player
+    pub fn expected(&self) -> f32 {
+        self.success * self.reward
+    }
+}
+
+/// Ranks all naked players up to the max level by the reward we expect to get
+/// from luring them. Players, that have already been lured today are left out
+/// and the results of earlier lures are taken into account
+pub fn plan_lures(
+    naked: &BTreeMap<u16, IntSet<u32>>,
+    player_info: &IntMap<u32, CharacterInfo>,
+    max_level: u16,
+    unit_level: f32,
+    history: &History,
+    limit: usize,
+) -> Vec<LureCandidate> {
+    let lure_yield = history.lure_yield();
+    let mut candidates: Vec<_> = naked
+        .range(..=max_level)
+        .flat_map(|(_, players)| players.iter())
+        .filter(|uid| !history.lured_today(**uid))
+        .filter_map(|uid| player_info.get(uid))
+        .map(|info| {
+            let previous = history.lures_against(info.uid);
+            LureCandidate::new(info, unit_level, previous, lure_yield)
+        })
+        .collect();
+
+    // Every naked player is ranked, before we only keep the best ones
+    candidates.sort_by(|a, b| {
+        b.expected()
+            .total_cmp(&a.expected())
+            .then(b.info.level.cmp(&a.info.level))
+    });
+    candidates.truncate(limit);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use sf_api::gamestate::{
+        items::EquipmentSlot, unlockables::EquipmentIdent,
+    };
+
+    use super::*;
+    use crate::history::{HistoryEntry, HistoryKind};
+
+    fn character(uid: u32, level: u16, items: usize) -> CharacterInfo {
+        CharacterInfo {
+            equipment: vec![
+                EquipmentIdent {
+                    class: None,
+                    typ: EquipmentSlot::Hat,
+                    model_id: 1,
+                    color: 0,
+                };
+                items
+            ],
+            name: format!("player{uid}"),
+            uid,
+            level,
+            stats: None,
+            fetch_date: None,
+            class: None,
+        }
+    }
+
+    fn lure(uid: u32, level: u16, won: bool, silver: u64) -> HistoryEntry {
+        HistoryEntry {
+            time: Local::now() - chrono::Duration::days(2),
+            kind: HistoryKind::Lure,
+            opponent: format!("player{uid}"),
+            opponent_uid: uid,
+            won,
+            items: 0,
+            mushrooms: 0,
+            silver,
+            souls: silver / 10,
+            opponent_level: level,
+            opponent_items: 0,
+        }
+    }
+
+    fn success(level: u16, items: usize, unit_level: f32) -> f32 {
+        let info = character(1, level, items);
+        LureCandidate::new(&info, unit_level, None, &LureYield::default())
+            .success
+    }
+
+    #[test]
+    fn success_estimate() {
+        assert!((success(100, 0, 100.0) - 0.5).abs() < 0.001);
+        // Stronger units make a win more likely
+        assert!(success(100, 0, 120.0) > success(100, 0, 110.0));
+        assert!(success(100, 0, 110.0) > success(100, 0, 100.0));
+        // Stronger victims make it less likely
+        assert!(success(120, 0, 100.0) < success(110, 0, 100.0));
+        assert!(success(100, 2, 100.0) < success(100, 1, 100.0));
+        let chance = success(500, 10, 100.0);
+        assert!(chance > 0.0 && chance < 0.001);
+    }
+
+    #[test]
+    fn previous_lures() {
+        let info = character(1, 100, 0);
+        let estimate = |won, lost| {
+            let previous = LureStats {
+                won,
+                lost,
+                ..Default::default()
+            };
+            let lure_yield = LureYield::default();
+            LureCandidate::new(&info, 100.0, Some(previous), &lure_yield)
+                .success
+        };
+        assert!(estimate(1, 0) > 0.5);
+        assert!(estimate(3, 0) > estimate(1, 0));
+        assert!(estimate(0, 1) < 0.5);
+        assert!(estimate(0, 3) < estimate(0, 1));
+        assert!((estimate(2, 2) - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn ranking() {
+        let candidates = [
+            character(1, 50, 0),
+            character(2, 100, 0),
+            character(3, 150, 0),
+            character(4, 100, 3),
+            character(5, 300, 0),
+        ];
+        let mut naked: BTreeMap<u16, IntSet<u32>> = BTreeMap::new();
+        for info in &candidates {
+            naked.entry(info.level).or_default().insert(info.uid);
+        }
+        let player_info: IntMap<u32, CharacterInfo> =
+            candidates.into_iter().map(|a| (a.uid, a)).collect();
+        let history = History::default();
+
+        // Without any lure results, the level of the victim is the reward
+        let res = plan_lures(&naked, &player_info, 200, 100.0, &history, 10);
+        let order: Vec<_> = res.iter().map(|a| a.info.uid).collect();
+        assert_eq!(order, [2, 1, 4, 3]);
+        for pair in res.windows(2) {
+            assert!(pair[0].expected() >= pair[1].expected());
+        }
+
+        let res = plan_lures(&naked, &player_info, 200, 100.0, &history, 2);
+        let order: Vec<_> = res.iter().map(|a| a.info.uid).collect();
+        assert_eq!(order, [2, 1]);
+    }
+
+    #[test]
+    fn ranking_with_history() {
+        let candidates = [character(1, 100, 0), character(2, 100, 0)];
+        let mut naked: BTreeMap<u16, IntSet<u32>> = BTreeMap::new();
+        naked.insert(100, [1, 2].into_iter().collect());
+        let player_info: IntMap<u32, CharacterInfo> =
+            candidates.into_iter().map(|a| (a.uid, a)).collect();
+
+        // Player 2 has paid out a lot more, than the average lure
+        let mut history = History::default();
+        history.push(lure(1, 100, true, 1_000));
+        history.push(lure(2, 100, true, 10_000));
+        let res = plan_lures(&naked, &player_info, 200, 100.0, &history, 10);
+        let order: Vec<_> = res.iter().map(|a| a.info.uid).collect();
+        assert_eq!(order, [2, 1]);
+        assert_eq!(res[0].estimate, Some((10_000.0, 1_000.0)));
+
+        // Players, that have already been lured today, are left out
+        history.push(HistoryEntry {
+            time: Local::now(),
+            ..lure(2, 100, true, 10_000)
+        });
+        let res = plan_lures(&naked, &player_info, 200, 100.0, &history, 10);
+        let order: Vec<_> = res.iter().map(|a| a.info.uid).collect();
+        assert_eq!(order, [1]);
+    }
+}
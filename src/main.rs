@@ -1,23 +1,44 @@
 #![windows_subsystem = "windows"]
+mod api;
 mod backup;
+mod completion;
 mod config;
 mod crawler;
+mod history;
+mod jobs;
 mod login;
+mod lure;
 mod message;
+mod metrics;
+mod notify;
 mod player;
+mod profile;
+mod progress;
+mod schedule;
+mod select;
 mod server;
+mod shutdown;
+mod targets;
 mod ui;
+mod vault;
 
 use std::{
     collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
     sync::{atomic::AtomicU64, Arc, Mutex},
     time::Duration,
 };
 
+use backup::{RestoreData, ZHofBackup};
 use chrono::{Local, NaiveDate, Utc};
 use clap::{Parser, Subcommand};
-use config::{AccountConfig, Config};
-use crawler::{CrawlAction, Crawler, CrawlerState, CrawlingOrder, WorkerQue};
+use completion::calc_completion;
+use config::{AccountConfig, Config, NakedRules};
+use crawler::{
+    CrawlAction, Crawler, CrawlerError, CrawlerState, CrawlingOrder, WorkerQue,
+};
+use history::HistoryFilter;
 use iced::{
     executor, subscription, theme,
     widget::{button, container, horizontal_space, row, text},
@@ -25,41 +46,63 @@ use iced::{
     Theme,
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use log::{debug, info, trace};
+use jobs::{CrawlScheduler, ScheduledJob};
+use log::{debug, error, info, trace};
 use log4rs::{
     append::{
         console::{ConsoleAppender, Target},
         file::FileAppender,
     },
     config::{Appender, Logger, Root},
-    encode::pattern::PatternEncoder,
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
 };
 use login::{LoginState, LoginType, PlayerAuth, SSOStatus, SSOValidator};
+use lure::plan_lures;
+use metrics::Metrics;
 use nohash_hasher::{IntMap, IntSet};
+use notify::Notifier;
 use player::{
-    AccountInfo, AccountStatus, AutoAttackChecker, AutoLureChecker, AutoPoll,
-    ScrapbookInfo,
+    avg_unit_level, AccountInfo, AccountStatus, AutoAttackChecker,
+    AutoLureChecker, AutoPoll, ScrapbookInfo,
+};
+use profile::{Profiles, DEFAULT_PROFILE};
+use progress::{
+    CrawlEvent, ProgressFormat, RunSummary, ServerProgress, ServerResult,
 };
+use schedule::{CronSpec, ScheduleKind};
 use serde::{Deserialize, Serialize};
 use server::{CrawlingStatus, ServerIdent, ServerInfo, Servers};
 use sf_api::{
     gamestate::{character::Class, unlockables::EquipmentIdent},
     session::ServerConnection,
-    sso::{SSOProvider, ServerLookup},
+    sso::SSOProvider,
 };
 use tokio::time::sleep;
 
 use crate::{
     config::{AccountCreds, AvailableTheme},
     message::Message,
+    select::ServerSelect,
 };
 pub const PER_PAGE: usize = 51;
+/// The environment variable, that can contain the master password
+const MASTER_PASSWORD_ENV: &str = "SF_HELPER_MASTER_PASSWORD";
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[command(subcommand)]
     pub sub: Option<CLICommand>,
+    /// The config file to use
+    #[arg(long, global = true, default_value = "helper.toml")]
+    pub config: PathBuf,
+    /// The profile to use. Each profile has its own accounts & settings
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// A file containing the master password of the config. The
+    /// SF_HELPER_MASTER_PASSWORD environment variable can be used instead
+    #[arg(long, global = true)]
+    pub key_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand, Clone)]
@@ -73,37 +116,141 @@ enum CLICommand {
         threads: usize,
         #[clap(flatten)]
         servers: ServerSelect,
+        #[clap(flatten)]
+        options: CrawlOptions,
+        /// Repeats the crawl whenever this cron schedule matches (i.e.
+        /// `0 3 * * *` for every night at 3), instead of exiting once it is
+        /// done
+        #[arg(long)]
+        cron: Option<CronSpec>,
+    },
+    /// Runs the crawl jobs from the config on their schedule and keeps
+    /// running between them
+    Schedule {
+        /// Only runs the jobs with these names
+        #[arg(short, long)]
+        job: Vec<String>,
+        /// How the progress of the jobs is reported. `json` prints one JSON
+        /// object per event on stdout
+        #[arg(long, value_enum, default_value_t)]
+        progress: ProgressFormat,
+    },
+    /// Logs in all characters, that are set to login automatically and runs
+    /// their auto-battle & auto-lure without a window
+    Run {
+        /// Writes the logs to stderr as one JSON object per line
+        #[arg(long)]
+        json_logs: bool,
     },
+    /// Prints the best scrapbook targets of a character from a HoF backup
+    Targets(targets::TargetArgs),
 }
 fn concurrency_limits(s: &str) -> Result<usize, String> {
     clap_num::number_range(s, 1, 50)
 }
 
 #[derive(Debug, clap::Args, Clone)]
-#[group(required = true, multiple = false)]
-pub struct ServerSelect {
-    /// Fetches a list of all servers and crawls all of them. Supercedes urls
+pub struct CrawlOptions {
+    /// The order in which the HoF pages are crawled. Defaults to random, or
+    /// the order of the resumed backup
+    #[arg(short, long, value_enum)]
+    order: Option<CrawlingOrder>,
+    /// Only crawl players at, or above this level. Defaults to 1, or the
+    /// level of the resumed backup
+    #[arg(long)]
+    min_level: Option<u32>,
+    /// Only crawl players at, or below this level. Defaults to 9999, or the
+    /// level of the resumed backup
+    #[arg(long)]
+    max_level: Option<u32>,
+    /// Continues unfinished crawls from the backups in the output directory,
+    /// instead of starting from scratch. Servers with a complete backup are
+    /// skipped
     #[arg(short, long)]
-    all: bool,
-    /// The list of all server urls to fetch
-    #[arg(short, long, value_delimiter = ' ', num_args = 1..)]
-    urls: Option<Vec<String>>,
+    resume: bool,
+    /// The directory the backups are written to & resumed from
+    #[arg(long, default_value = ".")]
+    output: PathBuf,
+    /// Saves the progress of all servers and exits after this many minutes
+    #[arg(long)]
+    time_budget: Option<u64>,
+    /// How the progress is reported. `json` prints one JSON object per
+    /// event on stdout
+    #[arg(long, value_enum, default_value_t)]
+    progress: ProgressFormat,
+}
+
+impl CrawlOptions {
+    /// The file the backup of this server is written to
+    pub fn backup_path(&self, server_ident: &str) -> PathBuf {
+        self.output.join(format!("{server_ident}.zhof"))
+    }
 }
 
 impl Args {
     pub fn is_headless(&self) -> bool {
         self.sub.is_some()
     }
+
+    /// The config of the selected profile
+    fn config_path(&self) -> Result<PathBuf, String> {
+        let profile = self.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+        Profiles::new(self.config.clone()).path(profile)
+    }
+
+    /// The master password from the key file, or the environment
+    fn master_password(&self) -> Option<Result<String, String>> {
+        if let Some(path) = &self.key_file {
+            return Some(
+                std::fs::read_to_string(path)
+                    .map(|a| a.trim_end_matches(['\r', '\n']).to_string())
+                    .map_err(|e| format!("Could not read {path:?}: {e}")),
+            );
+        }
+        std::env::var(MASTER_PASSWORD_ENV).ok().map(Ok)
+    }
+
+    /// The format of the logs on stderr. None, if stderr is already used for
+    /// something else
+    fn stderr_log(&self) -> Option<LogFormat> {
+        match &self.sub {
+            None => Some(LogFormat::Text),
+            Some(
+                CLICommand::Crawl { .. }
+                | CLICommand::Schedule { .. }
+                | CLICommand::Targets(_),
+            ) => None,
+            Some(CLICommand::Run { json_logs: true }) => Some(LogFormat::Json),
+            Some(CLICommand::Run { json_logs: false }) => Some(LogFormat::Text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LogFormat {
+    Text,
+    Json,
 }
 
 fn main() -> iced::Result {
     let args = Args::parse();
 
     let is_headless = args.is_headless();
-    let config = get_log_config(is_headless);
+    let config = get_log_config(args.stderr_log());
     log4rs::init_config(config).unwrap();
     info!("Starting up");
 
+    if let Some(CLICommand::Targets(targets)) = &args.sub {
+        let config = match args.config_path() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+        std::process::exit(targets::print_targets(targets.clone(), &config));
+    }
+
     let mut settings = Settings::with_flags(args);
     settings.window.min_size = Some(iced::Size {
         width: 700.0,
@@ -111,6 +258,8 @@ fn main() -> iced::Result {
     });
     settings.default_text_size = 13.0f32.into();
     settings.window.visible = !is_headless;
+    // We save the crawling progress, before the window actually closes
+    settings.window.exit_on_close_request = is_headless;
 
     let raw_img = include_bytes!("../assets/icon.ico");
     let img =
@@ -134,6 +283,29 @@ struct Helper {
     should_update: bool,
     class_images: ClassImages,
     cli_crawling: Option<CLICrawling>,
+    crawl_scheduler: Option<CrawlScheduler>,
+    /// Running without a window through the `run` command. This keeps all
+    /// characters logged in, regardless of the auto-poll setting
+    daemon: bool,
+    /// A shutdown was requested, so the crawlers are stopped and their
+    /// progress is saved, before we exit
+    shutting_down: bool,
+    pending_backups: usize,
+    history_filter: HistoryFilter,
+    history_export: Option<String>,
+    notifier: Notifier,
+    metrics: Metrics,
+    new_webhook: String,
+    webhook_status: Option<String>,
+    /// The input for unlocking the config, or setting a new master password
+    master_password: String,
+    master_password_status: Option<String>,
+    /// The key for the master password is derived in the background
+    deriving_key: bool,
+    /// The address the API should listen on. This is only applied, once it
+    /// is submitted
+    api_bind: String,
+    profiles: Profiles,
 }
 
 struct CLICrawling {
@@ -141,6 +313,64 @@ struct CLICrawling {
     mbp: MultiProgress,
     threads: usize,
     active: usize,
+    options: CrawlOptions,
+    /// The time budget has run out, so no new servers are started
+    timed_out: bool,
+    /// Identifies this run, so that the timeout of a previous run does not
+    /// stop it
+    run: u64,
+    /// The crawl was stopped by a signal
+    interrupted: bool,
+    servers: HashMap<ServerID, ServerProgress>,
+    summary: RunSummary,
+}
+
+impl CLICrawling {
+    fn emit(&self, event: &CrawlEvent) {
+        progress::emit(self.options.progress, event)
+    }
+
+    /// Records, that the crawl of a server has ended
+    fn server_done(
+        &mut self,
+        server: ServerID,
+        result: ServerResult,
+    ) -> ServerProgress {
+        let progress = self.servers.remove(&server).unwrap_or_default();
+        self.summary.add(result, &progress);
+        progress
+    }
+
+    fn character_crawled(&mut self, server: &ServerIdent, remaining: usize) {
+        let Some(progress) = self.servers.get_mut(&server.id) else {
+            return;
+        };
+        progress.characters += 1;
+        if !progress.should_report() {
+            return;
+        }
+        let characters = progress.characters;
+        self.emit(&CrawlEvent::CharactersDone {
+            server: &server.ident,
+            characters,
+            remaining,
+        });
+    }
+
+    fn crawl_error(&mut self, server: &ServerIdent, error: &CrawlerError) {
+        let Some(progress) = self.servers.get_mut(&server.id) else {
+            return;
+        };
+        progress.errors += 1;
+        self.emit(&CrawlEvent::Error {
+            server: &server.ident,
+            kind: error.kind(),
+            error: match error {
+                CrawlerError::Generic(e) => e,
+                _ => error.kind(),
+            },
+        });
+    }
 }
 
 struct ClassImages {
@@ -225,6 +455,16 @@ enum View {
     },
     Login,
     Settings,
+    /// The config is encrypted and has to be unlocked with the master
+    /// password first
+    Unlock,
+    /// The config could not be read. Nothing is written, until the user
+    /// decides what to do about it
+    InvalidConfig {
+        error: String,
+    },
+    /// There are multiple profiles and none was chosen via `--profile`
+    SelectProfile,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -236,7 +476,9 @@ pub enum ActionSelection {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum AccountPage {
     Scrapbook,
+    Completion,
     Underworld,
+    History,
     Options,
 }
 
@@ -307,7 +549,38 @@ impl Application for Helper {
     type Flags = Args;
 
     fn new(flags: Args) -> (Self, iced::Command<Self::Message>) {
-        let config = Config::restore().unwrap_or_default();
+        let mut profiles = Profiles::new(flags.config.clone());
+        let path = match flags.config_path() {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+        if let Some(profile) = &flags.profile {
+            profiles.current.clone_from(profile);
+        }
+        // Everyone picks their own profile, if there is more than one
+        let select_profile = flags.profile.is_none()
+            && !flags.is_headless()
+            && profiles.available.len() > 1;
+
+        let (config, config_error) = match Config::restore(&path) {
+            Ok(config) => (config, None),
+            Err(e) if flags.is_headless() => {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(1);
+            }
+            // We never want to silently overwrite the config, so this has to
+            // be resolved in the UI first
+            Err(e) => {
+                let config = Config {
+                    path,
+                    ..Default::default()
+                };
+                (config, Some(e.to_string()))
+            }
+        };
         let mut helper = Helper {
             servers: Default::default(),
             login_state: LoginState {
@@ -331,6 +604,21 @@ impl Application for Helper {
             class_images: ClassImages::new(),
             config,
             cli_crawling: None,
+            crawl_scheduler: None,
+            daemon: false,
+            shutting_down: false,
+            pending_backups: 0,
+            history_filter: Default::default(),
+            history_export: None,
+            notifier: Notifier::default(),
+            metrics: Metrics::default(),
+            new_webhook: String::new(),
+            webhook_status: None,
+            master_password: String::new(),
+            master_password_status: None,
+            deriving_key: false,
+            api_bind: String::new(),
+            profiles,
         };
 
         let fetch_update =
@@ -339,102 +627,145 @@ impl Application for Helper {
             });
         let mut commands = vec![fetch_update];
 
+        let daemon = matches!(flags.sub, Some(CLICommand::Run { .. }));
+        let mut jobs = vec![];
         if let Some(CLICommand::Crawl {
             concurrency,
             threads,
             servers,
-        }) = flags.sub
+            options,
+            cron,
+        }) = &flags.sub
         {
-            let mut info = CLICrawling {
-                todo_servers: Vec::new(),
-                mbp: MultiProgress::new(),
-                active: concurrency,
-                threads,
-            };
-
-            if let Some(servers) = servers.urls {
-                info.todo_servers = servers;
-
-                for _ in 0..concurrency {
-                    commands.push(Command::perform(async {}, move |_| {
-                        Message::NextCLICrawling
-                    }))
+            if let (Some(min), Some(max)) =
+                (options.min_level, options.max_level)
+            {
+                if min > max {
+                    eprintln!("--min-level has to be at most --max-level");
+                    std::process::exit(1);
                 }
-            } else if servers.all {
-                let c = Command::perform(
-                    async {
-                        ServerLookup::fetch().await.ok().map(|a| {
-                            a.all()
-                                .into_iter()
-                                .map(|a| a.to_string())
-                                .filter(|a| a != "https://speed.sfgame.net/")
-                                .collect()
-                        })
-                    },
-                    move |servers| Message::CrawlAllRes {
-                        servers,
-                        concurrency,
-                    },
+            }
+            match cron {
+                Some(cron) => jobs.push(ScheduledJob {
+                    name: "crawl".to_string(),
+                    cron: cron.clone(),
+                    servers: servers.clone(),
+                    concurrency: *concurrency,
+                    threads: *threads,
+                    options: options.clone(),
+                }),
+                None => commands.push(helper.start_cli_run(
+                    0,
+                    servers.clone(),
+                    *concurrency,
+                    *threads,
+                    options.clone(),
+                )),
+            }
+        }
+        if let Some(CLICommand::Schedule { job, progress }) = &flags.sub {
+            for conf in &helper.config.crawl_jobs {
+                if !job.is_empty() && !job.contains(&conf.name) {
+                    continue;
+                }
+                match ScheduledJob::from_config(conf) {
+                    Ok(mut job) => {
+                        job.options.progress = *progress;
+                        jobs.push(job);
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        if matches!(
+            flags.sub,
+            Some(
+                CLICommand::Schedule { .. }
+                    | CLICommand::Crawl { cron: Some(_), .. }
+            )
+        ) {
+            match CrawlScheduler::new(jobs) {
+                Ok((scheduler, command)) => {
+                    helper.crawl_scheduler = Some(scheduler);
+                    commands.push(command);
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        if daemon {
+            helper.daemon = true;
+            if !helper.config.accounts.iter().any(|a| a.has_auto_login()) {
+                error!(
+                    "No characters are set to login automatically. Enable \
+                     auto-login for them in the GUI first"
                 );
-                commands.push(c);
+                std::process::exit(1);
             }
-            helper.cli_crawling = Some(info);
+            info!("Running headless");
         }
         commands.push(
             iced::font::load(iced_aw::BOOTSTRAP_FONT_BYTES)
                 .map(Message::FontLoaded),
         );
 
-        let mut loading = 0;
-
-        for acc in &helper.config.accounts {
-            match acc {
-                AccountConfig::Regular { config, .. } => {
-                    if config.login {
-                        let acc = acc.clone();
-                        loading += 1;
-                        commands.push(Command::perform(
-                            async move {
-                                sleep(Duration::from_millis(
-                                    (loading - 1) * 200,
-                                ))
-                                .await
-                            },
-                            move |_| Message::Login {
-                                account: acc,
-                                auto_login: true,
-                            },
-                        ));
-                    }
+        if select_profile {
+            helper.current_view = View::SelectProfile;
+            return (helper, Command::batch(commands));
+        }
+
+        if let Some(error) = config_error {
+            helper.current_view = View::InvalidConfig { error };
+            return (helper, Command::batch(commands));
+        }
+
+        if helper.config.is_locked() {
+            let unlocked = match flags.master_password() {
+                Some(Ok(password)) => {
+                    helper.config.unlock(&password).map_err(|e| e.to_string())
                 }
-                AccountConfig::SF { characters, .. } => {
-                    if characters.iter().any(|a| a.config.login) {
-                        loading += 1;
-                        let acc = acc.clone();
-                        commands.push(Command::perform(
-                            async move {
-                                sleep(Duration::from_millis(
-                                    (loading - 1) * 200,
-                                ))
-                                .await
-                            },
-                            move |_| Message::Login {
-                                account: acc,
-                                auto_login: true,
-                            },
-                        ));
+                Some(Err(e)) => Err(e),
+                None => Err(String::new()),
+            };
+            match unlocked {
+                Ok(()) => {}
+                // The crawler does not need the accounts, unless its
+                // passwords are encrypted as well
+                Err(e)
+                    if e.is_empty()
+                        && flags.is_headless()
+                        && !daemon
+                        && !helper.config.crawler.has_encrypted_passwords() =>
+                {
+                    return (helper, Command::batch(commands));
+                }
+                Err(e) if flags.is_headless() => {
+                    if e.is_empty() {
+                        eprintln!(
+                            "The config is encrypted. Set \
+                             {MASTER_PASSWORD_ENV}, or use --key-file"
+                        );
+                    } else {
+                        eprintln!("Could not unlock the config: {e}");
                     }
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    helper.current_view = View::Unlock;
+                    helper.master_password_status =
+                        (!e.is_empty()).then_some(e);
+                    return (helper, Command::batch(commands));
                 }
             }
         }
 
-        if loading > 0 {
-            helper.current_view = View::Overview {
-                selected: Default::default(),
-                action: Default::default(),
-            };
-        }
-
+        commands.push(helper.auto_login());
         (helper, Command::batch(commands))
     }
 
@@ -443,7 +774,11 @@ impl Application for Helper {
     }
 
     fn title(&self) -> String {
-        format!("Scrapbook Helper v{}", env!("CARGO_PKG_VERSION"))
+        let version = env!("CARGO_PKG_VERSION");
+        match self.profiles.current.as_str() {
+            DEFAULT_PROFILE => format!("Scrapbook Helper v{version}"),
+            profile => format!("Scrapbook Helper v{version} - {profile}"),
+        }
     }
 
     fn update(
@@ -476,10 +811,12 @@ impl Application for Helper {
         enum SubIdent {
             RefreshUI,
             AutoPoll(AccountIdent),
-            AutoBattle(AccountIdent),
+            AutoBattle(AccountIdent, bool),
             AutoLure(AccountIdent),
             SSOCheck(SSOProvider),
             Crawling(usize, ServerID),
+            Api(SocketAddr),
+            Shutdown,
         }
 
         let mut subs = vec![];
@@ -493,9 +830,25 @@ impl Application for Helper {
         );
         subs.push(subscription);
 
+        let subscription = subscription::unfold(
+            SubIdent::Shutdown,
+            (),
+            move |a: ()| async move {
+                shutdown::wait_for_signal().await;
+                (Message::ShutdownRequested, a)
+            },
+        );
+        subs.push(subscription);
+        subs.push(iced::event::listen_with(|event, _| match event {
+            iced::Event::Window(_, iced::window::Event::CloseRequested) => {
+                Some(Message::ShutdownRequested)
+            }
+            _ => None,
+        }));
+
         for (server_id, server) in &self.servers.0 {
             for acc in server.accounts.values() {
-                if self.config.auto_poll {
+                if self.config.auto_poll || self.daemon {
                     let subscription = subscription::unfold(
                         SubIdent::AutoPoll(acc.ident),
                         AutoPoll {
@@ -508,12 +861,28 @@ impl Application for Helper {
                 }
 
                 if let Some(si) = &acc.scrapbook_info {
-                    if si.auto_battle {
+                    if si.auto_battle
+                        && acc.schedule_active(
+                            ScheduleKind::Battle,
+                            &server.ident,
+                        )
+                    {
+                        let mushrooms = match &*acc.status.lock().unwrap() {
+                            AccountStatus::Idle(_, gs)
+                            | AccountStatus::Busy(gs, _) => {
+                                gs.character.mushrooms
+                            }
+                            _ => 0,
+                        };
+                        // The subscription gets restarted, whenever this
+                        // changes
+                        let use_mushrooms = acc.may_use_mushroom(mushrooms);
                         let subscription = subscription::unfold(
-                            SubIdent::AutoBattle(acc.ident),
+                            SubIdent::AutoBattle(acc.ident, use_mushrooms),
                             AutoAttackChecker {
                                 player_status: acc.status.clone(),
                                 ident: acc.ident,
+                                use_mushrooms,
                             },
                             move |a: AutoAttackChecker| async move {
                                 (a.check().await, a)
@@ -524,7 +893,10 @@ impl Application for Helper {
                 };
 
                 if let Some(ui) = &acc.underworld_info {
-                    if ui.auto_lure {
+                    if ui.auto_lure
+                        && acc
+                            .schedule_active(ScheduleKind::Lure, &server.ident)
+                    {
                         let subscription = subscription::unfold(
                             SubIdent::AutoLure(acc.ident),
                             AutoLureChecker {
@@ -598,6 +970,20 @@ impl Application for Helper {
             subs.push(subscription);
         }
 
+        let api = &self.config.api;
+        if api.enabled || api.metrics {
+            // The subscription gets restarted, whenever the address changes.
+            // The token is checked when handling the requests, so changing it
+            // does not need a new listener
+            let addr = SocketAddr::new(api.bind, api.port);
+            let subscription = subscription::channel(
+                SubIdent::Api(addr),
+                100,
+                move |output| api::serve(addr, output),
+            );
+            subs.push(subscription);
+        }
+
         Subscription::batch(subs)
     }
 }
@@ -607,6 +993,7 @@ impl Helper {
         &mut self,
         url: &str,
         threads: usize,
+        options: &CrawlOptions,
         pb: ProgressBar,
     ) -> Option<Command<Message>> {
         let ident = ServerIdent::new(url);
@@ -622,7 +1009,7 @@ impl Helper {
 
         let que_id = QueID::new();
 
-        let que = WorkerQue {
+        let mut que = WorkerQue {
             que_id,
             todo_pages: Default::default(),
             todo_accounts: Default::default(),
@@ -630,12 +1017,17 @@ impl Helper {
             invalid_accounts: Default::default(),
             in_flight_pages: Default::default(),
             in_flight_accounts: Default::default(),
-            order: Default::default(),
+            order: options.order.unwrap_or_default(),
             lvl_skipped_accounts: Default::default(),
-            min_level: Default::default(),
+            min_level: 1,
             max_level: 9999,
             self_init: true,
+            paused: false,
         };
+        que.set_level_range(
+            options.min_level.unwrap_or(1),
+            options.max_level.unwrap_or(9999),
+        );
 
         server.crawling = CrawlingStatus::Crawling {
             que_id,
@@ -648,13 +1040,144 @@ impl Helper {
             crawling_session: None,
             recent_failures: Default::default(),
         };
-        Some(server.set_threads(threads, &self.config.base_name))
+        Some(server.set_threads(threads, &self.config))
+    }
+
+    /// Starts crawling the next server in the CLI. If there is a status, the
+    /// crawl continues from there
+    fn start_cli_crawl(
+        &mut self,
+        url: &str,
+        status: Option<RestoreData>,
+    ) -> Command<Message> {
+        let Some(cli) = &self.cli_crawling else {
+            return Command::none();
+        };
+        if cli.timed_out || cli.interrupted {
+            return Command::perform(async {}, |_| Message::NextCLICrawling);
+        }
+        let pb = cli.mbp.add(ProgressBar::new_spinner());
+        let threads = cli.threads;
+        let options = cli.options.clone();
+        let resumed = status.is_some();
+        let res = match status {
+            Some(status) => {
+                self.resume_crawling(url, threads, &options, pb.clone(), status)
+            }
+            None => {
+                self.force_init_crawling(url, threads, &options, pb.clone())
+            }
+        };
+        let Some(cli) = &mut self.cli_crawling else {
+            return Command::none();
+        };
+        let ident = ServerIdent::new(url);
+        match res {
+            Some(s) => {
+                cli.servers.insert(ident.id, ServerProgress::default());
+                cli.emit(&CrawlEvent::ServerStarted {
+                    server: &ident.ident,
+                    url,
+                    resumed,
+                });
+                s
+            }
+            None => {
+                let error = format!("Could not init crawling on: {url}");
+                pb.println(&error);
+                pb.finish_and_clear();
+                cli.emit(&CrawlEvent::Error {
+                    server: &ident.ident,
+                    kind: "init",
+                    error: &error,
+                });
+                let progress = cli.server_done(ident.id, ServerResult::Failed);
+                cli.emit(&CrawlEvent::finished(
+                    &ident.ident,
+                    ServerResult::Failed,
+                    &progress,
+                ));
+                Command::perform(async {}, |_| Message::NextCLICrawling)
+            }
+        }
+    }
+
+    /// Continues the crawl of a server from a backup in the CLI
+    fn resume_crawling(
+        &mut self,
+        url: &str,
+        threads: usize,
+        options: &CrawlOptions,
+        pb: ProgressBar,
+        status: RestoreData,
+    ) -> Option<Command<Message>> {
+        let ident = ServerIdent::new(url);
+        let connection = ServerConnection::new(url)?;
+        pb.enable_steady_tick(Duration::from_millis(30));
+        pb.set_prefix(ident.ident.to_string());
+        set_full_bar(&pb, "Crawling", 0);
+        let server = self.servers.get_or_insert_default(
+            ident,
+            connection,
+            Some(pb.clone()),
+        );
+        server.crawling = status.into_status();
+        if let CrawlingStatus::Crawling { que, .. } = &server.crawling {
+            let mut que = que.lock().unwrap();
+            if let Some(order) = options.order {
+                que.order = order;
+                order.apply_order(&mut que.todo_pages);
+            }
+            // Only the levels given on the command line replace the ones
+            // from the backup
+            if options.min_level.is_some() || options.max_level.is_some() {
+                let min = options.min_level.unwrap_or(que.min_level);
+                let max = options.max_level.unwrap_or(que.max_level);
+                que.set_level_range(min, max);
+            }
+        }
+        Some(server.set_threads(threads, &self.config))
+    }
+
+    /// Continues the startup in the UI, once the config could be read
+    fn config_loaded(&mut self) -> Command<Message> {
+        self.login_state.login_typ = match self.config.accounts.is_empty() {
+            true => LoginType::Regular,
+            false => LoginType::Saved,
+        };
+        if self.config.is_locked() {
+            self.current_view = View::Unlock;
+            return Command::none();
+        }
+        self.current_view = View::Login;
+        self.auto_login()
+    }
+
+    fn is_headless(&self) -> bool {
+        self.daemon
+            || self.cli_crawling.is_some()
+            || self.crawl_scheduler.is_some()
     }
 
     fn has_accounts(&self) -> bool {
         self.servers.0.iter().any(|a| !a.1.accounts.is_empty())
     }
 
+    fn update_all_best(&mut self) -> Command<Message> {
+        let todo: Vec<_> = self
+            .servers
+            .0
+            .values()
+            .flat_map(|a| a.accounts.values())
+            .map(|a| a.ident)
+            .collect();
+        let mut commands = vec![];
+        for acc in todo {
+            commands.push(self.update_best(acc, false));
+        }
+        Command::batch(commands)
+    }
+
     fn update_best(
         &mut self,
         ident: AccountIdent,
@@ -695,23 +1218,28 @@ impl Helper {
 
         let result_limit = 50;
 
+        let mut blacklist_expired = false;
+
         if let Some(si) = &mut account.scrapbook_info {
+            let expiry_days = self.config.blacklist_expiry_days;
+            let old_len = si.blacklist.len();
+            si.blacklist.retain(|_, a| !a.is_expired(expiry_days));
+            blacklist_expired = old_len != si.blacklist.len();
+
+            let epic_weight = self.config.epic_weight();
             let per_player_counts = calc_per_player_count(
                 player_info, equipment, &si.scrapbook.items, si,
-                self.config.blacklist_threshold,
+                self.config.blacklist_threshold, epic_weight,
             );
             let mut best_players = find_best(
                 &per_player_counts, player_info, result_limit, &invalid,
             );
 
-            best_players.sort_by(|a, b| {
-                b.missing
-                    .cmp(&a.missing)
-                    .then(a.info.stats.cmp(&b.info.stats))
-                    .then(a.info.level.cmp(&b.info.level))
-            });
+            sort_targets(&mut best_players);
 
             si.best = best_players;
+            si.completion =
+                calc_completion(equipment, &si.scrapbook.items, epic_weight);
 
             for target in &si.best {
                 if target.is_old()
@@ -726,24 +1254,23 @@ impl Helper {
         };
 
         if let Some(ui) = &mut account.underworld_info {
-            ui.best.clear();
-            'a: for (_, players) in naked.range(..=ui.max_level).rev() {
-                for player in players.iter() {
-                    if ui.best.len() >= result_limit {
-                        break 'a;
-                    }
-                    let Some(info) = player_info.get(player) else {
-                        continue;
-                    };
-                    if info.is_old()
-                        && !lock.todo_accounts.contains(&info.name)
-                        && !lock.invalid_accounts.contains(&info.name)
-                        && !lock.in_flight_accounts.contains(&info.name)
-                    {
-                        has_old = true;
-                        lock.todo_accounts.push(info.name.to_string())
-                    }
-                    ui.best.push(info.to_owned());
+            ui.best = plan_lures(
+                naked,
+                player_info,
+                ui.max_level,
+                avg_unit_level(&ui.underworld),
+                &account.history,
+                result_limit,
+            );
+            for target in &ui.best {
+                let info = &target.info;
+                if info.is_old()
+                    && !lock.todo_accounts.contains(&info.name)
+                    && !lock.invalid_accounts.contains(&info.name)
+                    && !lock.in_flight_accounts.contains(&info.name)
+                {
+                    has_old = true;
+                    lock.todo_accounts.push(info.name.to_string())
                 }
             }
         }
@@ -751,10 +1278,146 @@ impl Helper {
 
         account.last_updated = Local::now();
 
+        plan_targets(&mut server.accounts);
+
+        let mut res = Command::none();
         if (has_old || player_info.is_empty()) && *threads == 0 {
-            return server.set_threads(1, &self.config.base_name);
+            res = server.set_threads(1, &self.config);
         }
-        Command::none()
+        if blacklist_expired {
+            self.persist_blacklist(ident);
+        }
+        res
+    }
+
+    /// Stores the current blacklist of the character in its config, if the
+    /// character has one
+    fn persist_blacklist(&mut self, ident: AccountIdent) {
+        let Some((_, account)) = self.servers.get_ident(&ident) else {
+            return;
+        };
+        let Some(si) = &account.scrapbook_info else {
+            return;
+        };
+        let Some(config) = self
+            .config
+            .get_char_conf_mut(&account.name, ident.server_id)
+        else {
+            return;
+        };
+        config.blacklist = si.blacklist.values().cloned().collect();
+        config.blacklist.sort_by_key(|a| a.uid);
+        _ = self.config.write();
+    }
+
+    /// Stores the naked rules of the server & rebuilds the list of lure
+    /// targets with them
+    fn apply_naked_rules(&mut self, server_id: ServerID) -> Command<Message> {
+        _ = self.config.write();
+        let Some(server) = self.servers.get_mut(&server_id) else {
+            return Command::none();
+        };
+        let rules = self.config.naked_rules(&server.ident.ident);
+        let CrawlingStatus::Crawling {
+            player_info, naked, ..
+        } = &mut server.crawling
+        else {
+            return Command::none();
+        };
+        *naked = build_naked_index(player_info, rules);
+
+        let todo: Vec<_> = server.accounts.values().map(|a| a.ident).collect();
+        let mut commands = vec![];
+        for acc in todo {
+            commands.push(self.update_best(acc, false));
+        }
+        Command::batch(commands)
+    }
+
+    /// Stores the automation settings of the character in its config, if the
+    /// character has one
+    fn persist_automation(&mut self, ident: AccountIdent) {
+        let Some((_, account)) = self.servers.get_ident(&ident) else {
+            return;
+        };
+        let Some(config) = self
+            .config
+            .get_char_conf_mut(&account.name, ident.server_id)
+        else {
+            return;
+        };
+        if let Some(si) = &account.scrapbook_info {
+            config.mushrooms = si.mushroom_policy;
+            config.battle_limits = si.limits;
+            config.battle_schedule = si.schedule;
+        }
+        if let Some(ui) = &account.underworld_info {
+            config.lure_schedule = ui.schedule;
+        }
+        _ = self.config.write();
+    }
+}
+
+/// Distributes the targets of the characters on a server, that run
+/// auto-battle, so that they do not all chase the same players. Targets are
+/// assigned greedily by the amount of items they are missing for each
+/// character, with the character, that can fight next winning ties. Only
+/// targets, that the limits of a character allow, are considered. Every
+/// character gets at most one target and the targets assigned to the other
+/// auto-battling characters are stored in `claimed`
+fn plan_targets(
+    accounts: &mut HashMap<AccountID, AccountInfo, ahash::RandomState>,
+) {
+    let mut candidates = vec![];
+    for acc in accounts.values() {
+        let Some(si) = &acc.scrapbook_info else {
+            continue;
+        };
+        if !si.auto_battle {
+            continue;
+        }
+        let (next_fight, own_level) = match &*acc.status.lock().unwrap() {
+            AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => (
+                gs.arena.next_free_fight.unwrap_or_default(),
+                gs.character.level,
+            ),
+            _ => continue,
+        };
+        for target in si.best.iter().filter(|a| {
+            !a.is_old() && si.limits.allows(a, own_level, &si.blacklist)
+        }) {
+            candidates.push((
+                target.score, next_fight, acc.ident.account, target.info.uid,
+            ));
+        }
+    }
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut planned = HashSet::new();
+    let mut claimed: IntMap<u32, AccountID> = IntMap::default();
+    for (_, _, account, uid) in candidates {
+        if planned.contains(&account) || claimed.contains_key(&uid) {
+            continue;
+        }
+        planned.insert(account);
+        claimed.insert(uid, account);
+    }
+
+    for acc in accounts.values_mut() {
+        let Some(si) = &mut acc.scrapbook_info else {
+            continue;
+        };
+        // Characters without auto-battle pick their targets themselves, so
+        // nothing is hidden from them
+        if !si.auto_battle {
+            si.claimed.clear();
+            continue;
+        }
+        si.claimed = claimed
+            .iter()
+            .filter(|a| *a.1 != acc.ident.account)
+            .map(|a| *a.0)
+            .collect();
     }
 }
 
@@ -772,16 +1435,23 @@ pub fn calc_per_player_count(
     scrapbook: &HashSet<EquipmentIdent>,
     si: &ScrapbookInfo,
     blacklist_th: usize,
-) -> IntMap<u32, usize> {
-    let mut per_player_counts = IntMap::default();
+    epic_weight: usize,
+) -> IntMap<u32, MissingCount> {
+    let mut per_player_counts: IntMap<u32, MissingCount> = IntMap::default();
     per_player_counts.reserve(player_info.len());
 
     for (eq, players) in equipment.iter() {
-        if scrapbook.contains(eq) || eq.model_id >= 100 {
+        if scrapbook.contains(eq) {
+            continue;
+        }
+        let weight = item_weight(eq, epic_weight);
+        if weight == 0 {
             continue;
         }
         for player in players.iter() {
-            *per_player_counts.entry(*player).or_insert(0) += 1;
+            let count = per_player_counts.entry(*player).or_default();
+            count.items += 1;
+            count.score += weight;
         }
     }
 
@@ -798,8 +1468,8 @@ pub fn calc_per_player_count(
             return false;
         }
 
-        if let Some((_, lost)) = si.blacklist.get(&info.uid) {
-            if *lost >= blacklist_th.max(1) {
+        if let Some(entry) = si.blacklist.get(&info.uid) {
+            if entry.is_banned(blacklist_th) {
                 return false;
             }
         }
@@ -808,6 +1478,83 @@ pub fn calc_per_player_count(
     per_player_counts
 }
 
+/// The order in which to attack players, so that every fight gives us as many
+/// new items as possible. Every target assumes, that all previous fights have
+/// been won
+pub fn battle_order(
+    player_info: &IntMap<u32, CharacterInfo>,
+    equipment: &HashMap<
+        EquipmentIdent,
+        HashSet<u32, ahash::RandomState>,
+        ahash::RandomState,
+    >,
+    scrapbook: &HashSet<EquipmentIdent>,
+    si: &ScrapbookInfo,
+    invalid: &HashSet<&str>,
+    blacklist_th: usize,
+    epic_weight: usize,
+) -> Vec<AttackTarget> {
+    let mut scrapbook = scrapbook.clone();
+    let mut per_player_counts = calc_per_player_count(
+        player_info, equipment, &scrapbook, si, blacklist_th, epic_weight,
+    );
+    let mut best = find_best(&per_player_counts, player_info, 1, invalid)
+        .into_iter()
+        .next();
+
+    let mut target_list = Vec::new();
+    while let Some(target) = best {
+        if target_list.len() > 300 || target.score == 0 {
+            break;
+        }
+
+        for eq in &target.info.equipment {
+            let weight = item_weight(eq, epic_weight);
+            if scrapbook.contains(eq) || weight == 0 {
+                continue;
+            }
+            let Some(players) = equipment.get(eq) else {
+                continue;
+            };
+            // We decrease the new equipment count of all players, that have
+            // the same item as the one we just "found"
+            for player in players {
+                let ppc = per_player_counts.entry(*player).or_default();
+                ppc.items = ppc.items.saturating_sub(1);
+                ppc.score = ppc.score.saturating_sub(weight);
+            }
+        }
+
+        scrapbook.extend(target.info.equipment.iter().copied());
+        target_list.push(target);
+        best = find_best(&per_player_counts, player_info, 1, invalid)
+            .into_iter()
+            .next();
+    }
+    target_list
+}
+
+/// Items with a model id at, or above this are epics
+pub const EPIC_MODEL_ID: u16 = 100;
+
+/// The new items a player would give us
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MissingCount {
+    /// The amount of items, that are not yet in the scrapbook
+    pub items: usize,
+    /// The sum of the weights of these items, which is used for ranking
+    pub score: usize,
+}
+
+/// The amount a missing item counts for, when ranking targets
+pub fn item_weight(eq: &EquipmentIdent, epic_weight: usize) -> usize {
+    if eq.model_id >= EPIC_MODEL_ID {
+        epic_weight
+    } else {
+        1
+    }
+}
+
 macro_rules! impl_unique_id {
     ($type:ty) => {
         impl $type {
@@ -861,7 +1608,7 @@ impl ServerInfo {
     pub fn set_threads(
         &mut self,
         new_count: usize,
-        base_name: &str,
+        config: &Config,
     ) -> Command<Message> {
         let CrawlingStatus::Crawling {
             threads,
@@ -876,14 +1623,13 @@ impl ServerInfo {
 
         *threads = new_count;
 
-        let base_name = base_name.to_string();
+        let login = config.crawler.login(&config.base_name, &self.ident);
         let con = self.connection.clone();
         let id = self.ident.id;
 
         if not_logged_in {
-            Command::perform(
-                CrawlerState::try_login(base_name, con),
-                move |res| match res {
+            Command::perform(CrawlerState::try_login(login, con), move |res| {
+                match res {
                     Ok(state) => Message::CrawlerStartup {
                         server: id,
                         state: Arc::new(state),
@@ -892,8 +1638,8 @@ impl ServerInfo {
                         server: id,
                         error: err.to_string(),
                     },
-                },
-            )
+                }
+            })
         } else {
             Command::none()
         }
@@ -902,6 +1648,9 @@ impl ServerInfo {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct AttackTarget {
+    /// The weighted amount of new items, which targets are ranked by
+    score: usize,
+    /// The amount of new items we would get from this target
     missing: usize,
     info: CharacterInfo,
 }
@@ -911,8 +1660,19 @@ impl AttackTarget {
     }
 }
 
+/// Sorts the targets by how much we want to attack them. The best targets
+/// are first and weaker players are preferred, if they give us the same
+pub fn sort_targets(targets: &mut [AttackTarget]) {
+    targets.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(a.info.stats.cmp(&b.info.stats))
+            .then(a.info.level.cmp(&b.info.level))
+    });
+}
+
 fn find_best(
-    per_player_counts: &IntMap<u32, usize>,
+    per_player_counts: &IntMap<u32, MissingCount>,
     player_info: &IntMap<u32, CharacterInfo>,
     max_out: usize,
     invalid: &HashSet<&str>,
@@ -921,22 +1681,23 @@ fn find_best(
     let mut max = 1;
     let mut counts = [(); 10].map(|_| vec![]);
     for (player, count) in per_player_counts.iter().map(|a| (*a.0, *a.1)) {
-        if max_out == 1 && count < max || count == 0 {
+        if max_out == 1 && count.score < max || count.score == 0 {
             continue;
         }
-        max = max.max(count);
-        counts[(count - 1).clamp(0, 9)].push(player);
+        max = max.max(count.score);
+        counts[(count.score - 1).clamp(0, 9)].push((player, count));
     }
 
     let mut best_players = Vec::new();
-    for (count, players) in counts.iter().enumerate().rev() {
+    for players in counts.iter().rev() {
         best_players.extend(
             players
                 .iter()
-                .flat_map(|a| player_info.get(a))
-                .filter(|a| !invalid.contains(&a.name.as_str()))
-                .map(|a| AttackTarget {
-                    missing: count + 1,
+                .flat_map(|(a, count)| Some((player_info.get(a)?, count)))
+                .filter(|(a, _)| !invalid.contains(&a.name.as_str()))
+                .map(|(a, count)| AttackTarget {
+                    score: count.score,
+                    missing: count.items,
                     info: a.to_owned(),
                 }),
         );
@@ -995,11 +1756,10 @@ pub fn handle_new_char_info(
     >,
     player_info: &mut IntMap<u32, CharacterInfo>,
     naked: &mut BTreeMap<u16, IntSet<u32>>,
+    naked_rules: &NakedRules,
 ) {
     let player_entry = player_info.entry(char.uid);
 
-    const EQ_CUTOFF: usize = 4;
-
     match player_entry {
         Entry::Occupied(mut old) => {
             // We have already seen this player. We have to remove the old info
@@ -1016,17 +1776,13 @@ pub fn handle_new_char_info(
                     .and_modify(|a| {
                         a.insert(char.uid);
                     })
-                    .or_insert_with(|| {
-                        HashSet::from_iter([char.uid].into_iter())
-                    });
-            }
-            if old_info.equipment.len() < EQ_CUTOFF {
-                naked.entry(old_info.level).and_modify(|a| {
-                    a.remove(&old_info.uid);
-                });
+                    .or_insert_with(|| HashSet::from_iter([char.uid]));
             }
+            naked.entry(old_info.level).and_modify(|a| {
+                a.remove(&old_info.uid);
+            });
 
-            if char.equipment.len() < EQ_CUTOFF {
+            if naked_rules.matches(&char) {
                 naked.entry(char.level).or_default().insert(char.uid);
             }
             old.insert(char);
@@ -1038,11 +1794,9 @@ pub fn handle_new_char_info(
                     .and_modify(|a| {
                         a.insert(char.uid);
                     })
-                    .or_insert_with(|| {
-                        HashSet::from_iter([char.uid].into_iter())
-                    });
+                    .or_insert_with(|| HashSet::from_iter([char.uid]));
             }
-            if char.equipment.len() < EQ_CUTOFF && char.level >= 100 {
+            if naked_rules.matches(&char) {
                 naked.entry(char.level).or_default().insert(char.uid);
             }
             v.insert(char);
@@ -1050,14 +1804,23 @@ pub fn handle_new_char_info(
     }
 }
 
-fn get_log_config(is_headless: bool) -> log4rs::Config {
+/// Builds the index of all naked players from scratch. This is used, when the
+/// rules for what counts as naked have changed
+pub fn build_naked_index(
+    player_info: &IntMap<u32, CharacterInfo>,
+    naked_rules: &NakedRules,
+) -> BTreeMap<u16, IntSet<u32>> {
+    let mut naked: BTreeMap<u16, IntSet<u32>> = BTreeMap::new();
+    for char in player_info.values().filter(|a| naked_rules.matches(a)) {
+        naked.entry(char.level).or_default().insert(char.uid);
+    }
+    naked
+}
+
+fn get_log_config(stderr_log: Option<LogFormat>) -> log4rs::Config {
     let pattern = PatternEncoder::new(
         "{d(%Y-%m-%d %H:%M:%S)} | {({l}):5.5} | {M}:{L} | {m}{n}",
     );
-    let stderr = ConsoleAppender::builder()
-        .target(Target::Stderr)
-        .encoder(Box::new(pattern.clone()))
-        .build();
 
     let logfile = FileAppender::builder()
         .encoder(Box::new(pattern.clone()))
@@ -1068,7 +1831,15 @@ fn get_log_config(is_headless: bool) -> log4rs::Config {
         .appender(Appender::builder().build("logfile", Box::new(logfile)));
     let mut root = Root::builder();
 
-    if !is_headless {
+    if let Some(format) = stderr_log {
+        let encoder: Box<dyn Encode> = match format {
+            LogFormat::Text => Box::new(pattern.clone()),
+            LogFormat::Json => Box::new(JsonEncoder::new()),
+        };
+        let stderr = ConsoleAppender::builder()
+            .target(Target::Stderr)
+            .encoder(encoder)
+            .build();
         logger = logger
             .appender(Appender::builder().build("stderr", Box::new(stderr)));
         root = root.appender("stderr");
@@ -1131,3 +1902,174 @@ pub fn set_full_bar(bar: &ProgressBar, title: &str, length: usize) {
     bar.set_length(length as u64);
     bar.set_position(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use sf_api::gamestate::{items::EquipmentSlot, unlockables::ScrapBook};
+
+    use super::*;
+    use crate::config::NakedLimits;
+
+    fn item(typ: EquipmentSlot, model_id: u16) -> EquipmentIdent {
+        EquipmentIdent {
+            class: None,
+            typ,
+            model_id,
+            color: 0,
+        }
+    }
+
+    fn character(
+        uid: u32,
+        level: u16,
+        equipment: &[EquipmentIdent],
+    ) -> CharacterInfo {
+        CharacterInfo {
+            equipment: equipment.to_vec(),
+            name: format!("player{uid}"),
+            uid,
+            level,
+            stats: None,
+            fetch_date: None,
+            class: None,
+        }
+    }
+
+    #[test]
+    fn default_naked_rules() {
+        let rules = NakedRules::default();
+        let hat = item(EquipmentSlot::Hat, 1);
+        let cases = [
+            (character(1, 100, &[]), true),
+            (character(2, 99, &[]), false),
+            (character(3, 500, &[hat, hat, hat]), true),
+            (character(4, 500, &[hat, hat, hat, hat]), false),
+        ];
+        for (char, expected) in cases {
+            assert_eq!(rules.matches(&char), expected, "{char:?}");
+        }
+    }
+
+    #[test]
+    fn custom_naked_rules() {
+        let rules = NakedRules {
+            limits: NakedLimits {
+                max_items: 1,
+                min_level: 10,
+                max_level: 20,
+            },
+            empty_slots: vec![EquipmentSlot::Weapon],
+        };
+        let hat = item(EquipmentSlot::Hat, 1);
+        let weapon = item(EquipmentSlot::Weapon, 1);
+        let cases = [
+            (character(1, 10, &[]), true),
+            (character(2, 20, &[hat]), true),
+            (character(3, 21, &[]), false),
+            (character(4, 9, &[]), false),
+            (character(5, 15, &[weapon]), false),
+            (character(6, 15, &[hat, hat]), false),
+        ];
+        for (char, expected) in cases {
+            assert_eq!(rules.matches(&char), expected, "{char:?}");
+        }
+    }
+
+    #[test]
+    fn naked_index() {
+        let hat = item(EquipmentSlot::Hat, 1);
+        let player_info: IntMap<u32, CharacterInfo> = [
+            character(1, 100, &[]),
+            character(2, 100, &[hat]),
+            character(3, 150, &[]),
+            character(4, 50, &[]),
+            character(5, 150, &[hat, hat, hat, hat]),
+        ]
+        .into_iter()
+        .map(|a| (a.uid, a))
+        .collect();
+
+        let index = build_naked_index(&player_info, &NakedRules::default());
+        let expected: BTreeMap<u16, IntSet<u32>> = [
+            (100, [1, 2].into_iter().collect()),
+            (150, [3].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(index, expected);
+
+        // The index has to be the same, as if every player had been crawled
+        // one after another
+        let mut equipment = Default::default();
+        let mut crawled = Default::default();
+        let mut naked = Default::default();
+        for char in player_info.values() {
+            handle_new_char_info(
+                char.clone(),
+                &mut equipment,
+                &mut crawled,
+                &mut naked,
+                &NakedRules::default(),
+            );
+        }
+        assert_eq!(naked, expected);
+    }
+
+    #[test]
+    fn weighted_targets() {
+        let epic = item(EquipmentSlot::Weapon, EPIC_MODEL_ID);
+        let hat = item(EquipmentSlot::Hat, 1);
+        let boots = item(EquipmentSlot::FootWear, 1);
+        let found = item(EquipmentSlot::Gloves, 1);
+
+        let mut equipment = Default::default();
+        let mut player_info = Default::default();
+        let mut naked = Default::default();
+        for char in [
+            character(1, 100, &[epic]),
+            character(2, 100, &[hat, boots, found]),
+        ] {
+            handle_new_char_info(
+                char,
+                &mut equipment,
+                &mut player_info,
+                &mut naked,
+                &NakedRules::default(),
+            );
+        }
+        let scrapbook = ScrapBook {
+            items: [found].into_iter().collect(),
+            monster: Default::default(),
+        };
+        let si = ScrapbookInfo::from_scrapbook(scrapbook, 1000, 1_000_000);
+
+        // Missing items, score & if the epic should be first
+        let cases = [(3, [(1, 3), (2, 2)], true), (1, [(1, 1), (2, 2)], false)];
+        for (epic_weight, expected, epic_first) in cases {
+            let counts = calc_per_player_count(
+                &player_info, &equipment, &si.scrapbook.items, &si, 1,
+                epic_weight,
+            );
+            for (uid, (items, score)) in [1, 2].into_iter().zip(expected) {
+                let count = counts[&uid];
+                assert_eq!(count.items, items, "{epic_weight} {uid}");
+                assert_eq!(count.score, score, "{epic_weight} {uid}");
+            }
+
+            let best = find_best(&counts, &player_info, 10, &HashSet::new());
+            assert_eq!(best.len(), 2);
+            assert_eq!(best[0].info.uid == 1, epic_first, "{epic_weight}");
+            // The displayed count never includes the weight
+            let missing: Vec<_> =
+                best.iter().map(|a| (a.info.uid, a.missing)).collect();
+            assert!(missing.contains(&(1, 1)) && missing.contains(&(2, 2)));
+        }
+
+        // Epics are not counted at all, when they are ignored
+        let counts = calc_per_player_count(
+            &player_info, &equipment, &si.scrapbook.items, &si, 1, 0,
+        );
+        assert!(!counts.contains_key(&1));
+        assert_eq!(counts[&2].items, 2);
+    }
+}
@@ -1,12 +1,15 @@
 use std::{fmt::Write, sync::Arc, time::Duration};
 
 use chrono::Local;
-use config::{CharacterConfig, SFAccCharacter, SFCharIdent};
+use config::{
+    BattleLimits, BlacklistEntry, CharacterConfig, EpicPolicy, MushroomPolicy,
+    NakedLimits, SFAccCharacter, SFCharIdent,
+};
 use crawler::CrawlerError;
 use iced::Command;
-use log::{error, trace, warn};
+use log::{error, info, trace, warn};
 use sf_api::{
-    gamestate::GameState,
+    gamestate::{items::EquipmentSlot, GameState},
     session::{PWHash, Response, Session},
     sso::SSOProvider,
 };
@@ -15,12 +18,17 @@ use ui::OverviewAction;
 
 use self::{
     backup::{get_newest_backup, restore_backup, RestoreData},
+    history::{History, HistoryEntry, HistoryFilter, HistoryKind},
     login::{SSOIdent, SSOLogin, SSOLoginStatus},
+    notify::{send_event, NotifyEvent, NotifyKind, Webhook, WebhookFormat},
+    schedule::{Schedule, ScheduleKind},
     ui::underworld::LureTarget,
+    vault::{Vault, VaultHeader},
 };
 use crate::{
+    api::{generate_token, token_matches, ApiConnection, ApiRequest},
     crawler::CrawlerState,
-    player::{ScrapbookInfo, UnderworldInfo},
+    player::{ScrapbookInfo, UnderworldInfo, BATTLE_STUCK_MINUTES},
     *,
 };
 
@@ -30,11 +38,25 @@ pub enum Message {
         action: OverviewAction,
     },
     FontLoaded(Result<(), iced::font::Error>),
-    CrawlAllRes {
-        servers: Option<Vec<String>>,
+    CrawlServersRes {
+        servers: Result<Vec<String>, String>,
         concurrency: usize,
     },
     NextCLICrawling,
+    CLICrawlResume {
+        url: String,
+        /// The backup of the server is already complete, so there is nothing
+        /// left to crawl
+        complete: bool,
+        status: Option<Box<RestoreData>>,
+    },
+    CLICrawlTimeout(u64),
+    CrawlJobDue(usize),
+    ShutdownRequested,
+    ShutdownWait(u32),
+    ShutdownBackupWritten {
+        error: Option<String>,
+    },
     AdvancedLevelRestrict(bool),
     ShowClasses(bool),
     CrawlerSetMinMax {
@@ -107,6 +129,18 @@ pub enum Message {
     SetMaxThreads(usize),
     SetStartThreads(usize),
     SetBlacklistThr(usize),
+    SetBlacklistExpiry(u32),
+    PlayerBlacklistAdd {
+        ident: AccountIdent,
+        uid: u32,
+        name: String,
+    },
+    PlayerBlacklistRemove {
+        ident: AccountIdent,
+        uid: u32,
+    },
+    SetEpicPolicy(EpicPolicy),
+    SetEpicWeight(usize),
     SetAutoFetch(bool),
     SetAutoPoll(bool),
     ViewSubPage {
@@ -178,7 +212,9 @@ pub enum Message {
         server: ServerID,
         new_count: usize,
     },
-    PageCrawled,
+    PageCrawled {
+        server: ServerID,
+    },
     RemoveAccount {
         ident: AccountIdent,
     },
@@ -246,14 +282,103 @@ pub enum Message {
         ident: AccountIdent,
     },
     SetAction(Option<ActionSelection>),
+    SetMushroomPolicy {
+        ident: AccountIdent,
+        policy: MushroomPolicy,
+    },
+    SetBattleLimits {
+        ident: AccountIdent,
+        limits: BattleLimits,
+    },
+    SetNakedLimits {
+        server: ServerID,
+        limits: NakedLimits,
+    },
+    SetNakedSlot {
+        server: ServerID,
+        slot: EquipmentSlot,
+        empty: bool,
+    },
+    ApplyNakedRules {
+        server: ServerID,
+    },
+    SetSchedule {
+        ident: AccountIdent,
+        kind: ScheduleKind,
+        schedule: Schedule,
+    },
+    SetHistoryFilter(HistoryFilter),
+    SetHistoryDays(u32),
+    SetNotifyEvent {
+        kind: NotifyKind,
+        enabled: bool,
+    },
+    SetNotifyCooldown(u32),
+    WebhookInput(String),
+    WebhookAdd,
+    WebhookRemove(usize),
+    WebhookSetFormat {
+        idx: usize,
+        format: WebhookFormat,
+    },
+    WebhookTest,
+    WebhookSent {
+        error: Option<String>,
+    },
+    ExportHistory {
+        ident: AccountIdent,
+    },
+    HistoryExported {
+        result: Result<String, String>,
+    },
+    ApiRequest {
+        request: ApiRequest,
+        conn: ApiConnection,
+    },
+    ApiResponded,
+    SetApiEnabled(bool),
+    SetMetricsEnabled(bool),
+    SetApiPort(u16),
+    ApiBindInput(String),
+    SetApiBind,
+    SetApiToken(String),
+    GenerateApiToken,
+    MasterPasswordInput(String),
+    UnlockConfig,
+    ConfigUnlocked(Result<Vault, String>),
+    SetMasterPassword,
+    MasterPasswordCreated(Result<(Vault, VaultHeader), String>),
+    RemoveMasterPassword,
+    ReloadConfig,
+    /// Moves the invalid config out of the way and starts with a new one
+    ResetConfig,
+    SwitchProfile(String),
+    ProfileNameInput(String),
+    CreateProfile,
 }
 
 impl Helper {
     pub fn handle_msg(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::UIActive => {}
-            Message::PageCrawled => {
-                // Gets handled in crawling
+            Message::PageCrawled { server } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                self.metrics.count_page(&server.ident.ident);
+                let Some(cli) = &mut self.cli_crawling else {
+                    return Command::none();
+                };
+                let Some(progress) = cli.servers.get_mut(&server.ident.id)
+                else {
+                    return Command::none();
+                };
+                progress.pages += 1;
+                let pages = progress.pages;
+                cli.emit(&CrawlEvent::PagesDone {
+                    server: &server.ident.ident,
+                    pages,
+                });
             }
             Message::CrawlerDied { server, error } => {
                 log::error!("Crawler died on {server} - {error}");
@@ -261,7 +386,43 @@ impl Helper {
                 let Some(server) = self.servers.get_mut(&server) else {
                     return Command::none();
                 };
-                server.crawling = CrawlingStatus::CrawlingFailed(error)
+                let event = NotifyEvent::new(
+                    NotifyKind::CrawlerDied,
+                    &server.ident.ident,
+                    None,
+                    &error,
+                );
+                let notify =
+                    self.notifier.notify(&self.config.notifications, event);
+                let (Some(pb), Some(cli)) =
+                    (server.headless_progress.clone(), &mut self.cli_crawling)
+                else {
+                    server.crawling = CrawlingStatus::CrawlingFailed(error);
+                    return notify;
+                };
+                // The CLI can not do anything about this, so we just move on
+                // to the next server
+                pb.println(&error);
+                pb.finish_and_clear();
+                let ident = &server.ident.ident;
+                cli.emit(&CrawlEvent::Error {
+                    server: ident,
+                    kind: "crawler_died",
+                    error: &error,
+                });
+                let progress =
+                    cli.server_done(server.ident.id, ServerResult::Failed);
+                cli.emit(&CrawlEvent::finished(
+                    ident,
+                    ServerResult::Failed,
+                    &progress,
+                ));
+                let id = server.ident.id;
+                self.servers.0.remove(&id);
+                return Command::batch([
+                    notify,
+                    Command::perform(async {}, |_| Message::NextCLICrawling),
+                ]);
             }
             Message::CharacterCrawled {
                 server,
@@ -273,6 +434,8 @@ impl Helper {
                 };
 
                 trace!("{} crawled {}", server.ident.ident, character.name);
+                self.metrics.count_character(&server.ident.ident);
+                let naked_rules = self.config.naked_rules(&server.ident.ident);
 
                 let CrawlingStatus::Crawling {
                     player_info,
@@ -296,6 +459,9 @@ impl Helper {
                         let total = remaining + crawled;
                         pb.set_length(total as u64);
                         pb.set_position(crawled as u64);
+                        if let Some(cli) = &mut self.cli_crawling {
+                            cli.character_crawled(&server.ident, remaining);
+                        }
                     };
                     lock.in_flight_accounts.remove(&character.name);
                     lock.todo_pages.is_empty() && lock.todo_accounts.is_empty()
@@ -312,7 +478,9 @@ impl Helper {
 
                 *last_update = Local::now();
 
-                handle_new_char_info(character, equipment, player_info, naked);
+                handle_new_char_info(
+                    character, equipment, player_info, naked, naked_rules,
+                );
 
                 if crawler_finished {
                     let mut commands = vec![];
@@ -352,11 +520,14 @@ impl Helper {
                     return Command::none();
                 }
                 let backup = lock.create_backup(player_info);
-                let ident = server.ident.ident.to_string();
+                let path = match &self.cli_crawling {
+                    Some(cli) => cli.options.backup_path(&server.ident.ident),
+                    None => format!("{}.zhof", server.ident.ident).into(),
+                };
                 let id = server.ident.id;
 
                 return Command::perform(
-                    async move { backup.write(&ident).await },
+                    async move { backup.write_path(path).await },
                     move |res| Message::BackupRes {
                         server: id,
                         error: res.err().map(|a| a.to_string()),
@@ -375,6 +546,10 @@ impl Helper {
                 let Some(server) = self.servers.get_mut(&server_id) else {
                     return Command::none();
                 };
+                self.metrics.count_crawl_error(&server.ident.ident, &error);
+                if let Some(cli) = &mut self.cli_crawling {
+                    cli.crawl_error(&server.ident, &error);
+                }
                 let CrawlingStatus::Crawling {
                     que_id,
                     que,
@@ -557,6 +732,11 @@ impl Helper {
                     self.config.get_char_conf(&player.name, ident.server_id);
 
                 player.scrapbook_info = ScrapbookInfo::new(&gs, char_conf);
+                player.history = History::load(
+                    &self.config.history_dir(),
+                    &server.ident.ident,
+                    &player.name,
+                );
                 player.underworld_info = UnderworldInfo::new(&gs, char_conf);
 
                 *player.status.lock().unwrap() =
@@ -565,6 +745,8 @@ impl Helper {
                 let server_ident = server.ident.ident.clone();
                 let server_id = server.ident.id;
                 let afn = self.config.auto_fetch_newest;
+                let naked_rules =
+                    self.config.naked_rules(&server_ident).clone();
                 match &server.crawling {
                     CrawlingStatus::Waiting => {
                         server.crawling = CrawlingStatus::Restoring;
@@ -573,7 +755,10 @@ impl Helper {
                                 let backup =
                                     get_newest_backup(server_ident, afn).await;
                                 Box::new(
-                                    restore_backup(backup, total_pages).await,
+                                    restore_backup(
+                                        backup, total_pages, naked_rules,
+                                    )
+                                    .await,
                                 )
                             },
                             move |backup| Message::ResetCrawling {
@@ -591,11 +776,19 @@ impl Helper {
             }
             Message::LoggininFailure { error, ident } => {
                 error!("Error loggin in {ident}: {error}");
-                let Some((_, player)) = self.servers.get_ident(&ident) else {
+                let Some((server, player)) = self.servers.get_ident(&ident)
+                else {
                     return Command::none();
                 };
+                let event = NotifyEvent::new(
+                    NotifyKind::FatalError,
+                    &server.ident.ident,
+                    Some(&player.name),
+                    format!("Could not log in: {error}"),
+                );
                 *player.status.lock().unwrap() =
-                    AccountStatus::FatalError(error)
+                    AccountStatus::FatalError(error);
+                return self.notifier.notify(&self.config.notifications, event);
             }
             Message::ShowPlayer { ident } => {
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
@@ -636,7 +829,7 @@ impl Helper {
                     CrawlingStatus::Waiting | CrawlingStatus::Restoring => {
                         server.crawling = status.into_status();
                         commands.push(server.set_threads(
-                            self.config.start_threads, &self.config.base_name,
+                            self.config.start_threads, &self.config,
                         ));
                     }
                     CrawlingStatus::Crawling {
@@ -727,7 +920,7 @@ impl Helper {
                     return Command::none();
                 };
 
-                return server.set_threads(new_count, &self.config.base_name);
+                return server.set_threads(new_count, &self.config);
             }
             Message::ClearHof(server_id) => {
                 let Some(server) = self.servers.get_mut(&server_id) else {
@@ -751,9 +944,13 @@ impl Helper {
                 let tp = (tp as usize).div_ceil(PER_PAGE);
 
                 let id = server.ident.id;
+                let naked_rules =
+                    self.config.naked_rules(&server.ident.ident).clone();
 
                 return Command::perform(
-                    async move { Box::new(restore_backup(None, tp).await) },
+                    async move {
+                        Box::new(restore_backup(None, tp, naked_rules).await)
+                    },
                     move |res| Message::ResetCrawling {
                         server: id,
                         status: res,
@@ -790,7 +987,7 @@ impl Helper {
                 }
             }
             Message::AutoBattlePossible { ident } => {
-                let refetch = self.update_best(ident, true);
+                let mut refetch = self.update_best(ident, true);
 
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
                 else {
@@ -805,21 +1002,71 @@ impl Helper {
                     return Command::none();
                 };
 
+                if let Some(si) = &mut account.scrapbook_info {
+                    if let Some(reason) = si.limits.stop_reason(
+                        si.consecutive_losses,
+                        si.scrapbook.items.len(),
+                    ) {
+                        info!("Stopping auto-battle for {ident}: {reason}");
+                        let event = NotifyEvent::new(
+                            NotifyKind::BattleStopped,
+                            &server.ident.ident,
+                            Some(&account.name),
+                            &reason,
+                        );
+                        si.auto_battle = false;
+                        si.stop_reason = Some(reason);
+                        si.waiting_since = None;
+                        return Command::batch([
+                            refetch,
+                            self.notifier
+                                .notify(&self.config.notifications, event),
+                        ]);
+                    }
+
+                    // This is set, once we try to fight and any fight result
+                    // resets it, so if this gets too old, our fights have not
+                    // been going through in a while
+                    if let Some(since) = si.waiting_since.filter(|since| {
+                        Local::now() - *since
+                            > chrono::Duration::minutes(BATTLE_STUCK_MINUTES)
+                    }) {
+                        let event = NotifyEvent::new(
+                            NotifyKind::BattleStuck,
+                            &server.ident.ident,
+                            Some(&account.name),
+                            format!(
+                                "No fight since {}",
+                                since.format("%Y-%m-%d %H:%M")
+                            ),
+                        );
+                        let notify = self
+                            .notifier
+                            .notify(&self.config.notifications, event);
+                        refetch = Command::batch([refetch, notify]);
+                    }
+                }
+
                 let mut status = account.status.lock().unwrap();
                 let AccountStatus::Idle(_, gs) = &*status else {
                     return refetch;
                 };
+                let own_level = gs.character.level;
+                let mushrooms = gs.character.mushrooms;
                 let next = gs.arena.next_free_fight.unwrap_or_default();
-                if next > Local::now() + Duration::from_millis(200) {
+                let use_mushroom =
+                    next > Local::now() + Duration::from_millis(200);
+                if use_mushroom && !account.may_use_mushroom(mushrooms) {
                     return refetch;
                 }
+                let spent_today = account.history.mushrooms_spent_today();
 
                 let Some(mut session) = status.take_session("A Fighting")
                 else {
                     return refetch;
                 };
 
-                let Some(si) = &account.scrapbook_info else {
+                let Some(si) = &mut account.scrapbook_info else {
                     status.put_session(session);
                     return refetch;
                 };
@@ -837,20 +1084,36 @@ impl Helper {
                     return refetch;
                 }
 
-                let Some(target) =
-                    si.best.iter().find(|a| !a.is_old()).cloned()
+                let Some(target) = si
+                    .best
+                    .iter()
+                    .find(|a| {
+                        !a.is_old()
+                            && !si.claimed.contains(&a.info.uid)
+                            && si.limits.allows(a, own_level, &si.blacklist)
+                    })
+                    .cloned()
                 else {
                     status.put_session(session);
                     return refetch;
                 };
+                if use_mushroom
+                    && !si
+                        .mushroom_policy
+                        .should_spend(spent_today, mushrooms, target.missing)
+                {
+                    status.put_session(session);
+                    return refetch;
+                }
                 drop(status);
+                si.waiting_since.get_or_insert(Local::now());
 
                 let tn = target.info.name.clone();
                 let fight = Command::perform(
                     async move {
                         let cmd = sf_api::command::Command::Fight {
                             name: tn,
-                            use_mushroom: false,
+                            use_mushroom,
                         };
                         let resp = session.send_command(&cmd).await;
                         (resp, session)
@@ -890,6 +1153,8 @@ impl Helper {
                 *lock = AccountStatus::LoggingInAgain;
                 drop(lock);
                 warn!("Logging in {ident} again");
+                self.metrics
+                    .count_relogin(&server.ident.ident, &player.name);
                 return Command::perform(
                     async move {
                         let Ok(resp) = session.login().await else {
@@ -937,11 +1202,21 @@ impl Helper {
                     return Command::none();
                 };
 
+                let mushrooms_before = s.character.mushrooms;
                 if let Err(e) = s.update(*resp) {
                     // it would *probably* be ok to just ignore this in most
                     // cases, but whatever
+                    let event = NotifyEvent::new(
+                        NotifyKind::FatalError,
+                        &server.ident.ident,
+                        Some(&account.name),
+                        e.to_string(),
+                    );
                     *lock = AccountStatus::FatalError(e.to_string());
-                    return Command::none();
+                    drop(lock);
+                    return self
+                        .notifier
+                        .notify(&self.config.notifications, event);
                 };
 
                 let Some(last) = &s.last_fight else {
@@ -950,16 +1225,59 @@ impl Helper {
 
                 let nt = against.info.name.clone();
                 let ut = against.info.uid;
+                info!(
+                    "{ident} {} a fight against {nt}",
+                    if last.has_player_won { "won" } else { "lost" }
+                );
 
                 let Some(si) = &mut account.scrapbook_info else {
                     return Command::none();
                 };
 
+                let mut new_items = 0;
+                let mut new_epics = 0;
                 if last.has_player_won {
                     for new in &against.info.equipment {
-                        si.scrapbook.items.insert(*new);
+                        if si.scrapbook.items.insert(*new) {
+                            new_items += 1;
+                            if new.model_id >= EPIC_MODEL_ID {
+                                new_epics += 1;
+                            }
+                        }
                     }
                 }
+                si.waiting_since = None;
+
+                let rare_event = (new_epics > 0).then(|| {
+                    NotifyEvent::new(
+                        NotifyKind::RareItem,
+                        &server.ident.ident,
+                        Some(&account.name),
+                        format!("Found {new_epics} new epic item(s) on {nt}"),
+                    )
+                });
+
+                self.metrics.count_fight(
+                    &server.ident.ident,
+                    &account.name,
+                    HistoryKind::Fight,
+                    last.has_player_won,
+                    mushrooms_before.saturating_sub(s.character.mushrooms),
+                );
+                account.history.push(HistoryEntry {
+                    time: Local::now(),
+                    kind: HistoryKind::Fight,
+                    opponent: nt.clone(),
+                    opponent_uid: ut,
+                    won: last.has_player_won,
+                    items: new_items,
+                    mushrooms: mushrooms_before
+                        .saturating_sub(s.character.mushrooms),
+                    silver: 0,
+                    souls: 0,
+                    opponent_level: against.info.level,
+                    opponent_items: against.info.equipment.len(),
+                });
 
                 si.attack_log.push((
                     Local::now(),
@@ -967,18 +1285,49 @@ impl Helper {
                     last.has_player_won,
                 ));
 
-                let mut res = Command::none();
-
-                if !last.has_player_won {
-                    si.blacklist.entry(ut).or_insert((nt, 0)).1 += 1;
-                } else if let CrawlingStatus::Crawling { .. } = &server.crawling
-                {
-                    let ident = account.ident;
-                    res = self.update_best(ident, false);
+                let lost = !last.has_player_won;
+                if lost {
+                    si.consecutive_losses += 1;
+                    let entry =
+                        si.blacklist.entry(ut).or_insert(BlacklistEntry {
+                            uid: ut,
+                            name: nt,
+                            losses: 0,
+                            last_loss: None,
+                            manual: false,
+                        });
+                    entry.losses += 1;
+                    entry.last_loss = Some(Local::now());
+                } else {
+                    si.consecutive_losses = 0;
                 }
 
                 lock.put_session(session);
-                return res;
+                drop(lock);
+
+                let is_crawling =
+                    matches!(server.crawling, CrawlingStatus::Crawling { .. });
+
+                if lost {
+                    self.persist_blacklist(ident);
+                }
+
+                let notify = match rare_event {
+                    Some(event) => {
+                        self.notifier.notify(&self.config.notifications, event)
+                    }
+                    None => Command::none(),
+                };
+
+                // The planner looks at all characters on this server, so this
+                // has to happen after we have released the lock
+                if is_crawling {
+                    return Command::batch([
+                        notify,
+                        self.update_best(ident, false),
+                    ]);
+                }
+                return notify;
             }
             Message::AutoBattle { ident, state } => {
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
@@ -995,6 +1344,11 @@ impl Helper {
                 };
 
                 si.auto_battle = state;
+                if state {
+                    si.consecutive_losses = 0;
+                    si.stop_reason = None;
+                    si.waiting_since = None;
+                }
             }
             Message::CrawlerStartup { server, state } => {
                 let Some(server) = self.servers.get_mut(&server) else {
@@ -1008,6 +1362,12 @@ impl Helper {
                     return Command::none();
                 };
                 *crawling_session = Some(state);
+                // The crawler might have been registered with a password, that
+                // only exists in memory so far
+                if self.config.unsaved_password {
+                    self.config.unsaved_password = false;
+                    _ = self.config.write();
+                }
             }
             Message::CrawlerRevived { server_id } => {
                 info!("Crawler revived");
@@ -1374,9 +1734,31 @@ impl Helper {
                 let Some(pb) = server.headless_progress.clone() else {
                     return Command::none();
                 };
-                if let Some(err) = error {
+                if let Some(err) = &error {
                     pb.println(err)
                 }
+                if let Some(cli) = &mut self.cli_crawling {
+                    let ident = &server.ident.ident;
+                    let result = match &error {
+                        Some(error) => {
+                            cli.emit(&CrawlEvent::Error {
+                                server: ident,
+                                kind: "backup",
+                                error,
+                            });
+                            ServerResult::Failed
+                        }
+                        None => {
+                            cli.emit(&CrawlEvent::BackupWritten {
+                                server: ident,
+                                path: &cli.options.backup_path(ident),
+                            });
+                            ServerResult::Complete
+                        }
+                    };
+                    let progress = cli.server_done(server_id, result);
+                    cli.emit(&CrawlEvent::finished(ident, result, &progress));
+                }
                 self.servers.0.remove(&server_id);
                 pb.finish_and_clear();
                 return Command::perform(async {}, |_| {
@@ -1403,50 +1785,21 @@ impl Helper {
                     return Command::none();
                 };
 
-                let mut best = si.best.first().cloned();
-                let mut scrapbook = si.scrapbook.items.clone();
-
-                let mut per_player_counts = calc_per_player_count(
-                    player_info, equipment, &scrapbook, si,
-                    self.config.blacklist_threshold,
-                );
-
-                let mut target_list = Vec::new();
-                let mut loop_count = 0;
                 let lock = que.lock().unwrap();
                 let invalid =
                     lock.invalid_accounts.iter().map(|a| a.as_str()).collect();
-
-                while let Some(AttackTarget { missing, info }) = best {
-                    if loop_count > 300 || missing == 0 {
-                        break;
-                    }
-                    loop_count += 1;
-
-                    for eq in &info.equipment {
-                        if scrapbook.contains(eq) {
-                            continue;
-                        }
-                        let Some(players) = equipment.get(eq) else {
-                            continue;
-                        };
-                        // We decrease the new equipment count of all players,
-                        // that have the same item as
-                        // the one we just "found"
-                        for player in players {
-                            let ppc =
-                                per_player_counts.entry(*player).or_insert(1);
-                            *ppc = ppc.saturating_sub(1);
-                        }
-                    }
-
-                    scrapbook.extend(info.equipment);
-                    target_list.push(info.name);
-                    let best_players =
-                        find_best(&per_player_counts, player_info, 1, &invalid);
-                    best = best_players.into_iter().next();
-                }
+                let target_list = battle_order(
+                    player_info,
+                    equipment,
+                    &si.scrapbook.items,
+                    si,
+                    &invalid,
+                    self.config.blacklist_threshold,
+                    self.config.epic_weight(),
+                );
                 drop(lock);
+                let target_list: Vec<_> =
+                    target_list.into_iter().map(|a| a.info.name).collect();
                 return iced::clipboard::write(target_list.join("/"));
             }
             Message::PlayerRelogSuccess { ident, gs, session } => {
@@ -1552,11 +1905,24 @@ impl Helper {
                     return Command::none();
                 };
 
+                let mushrooms_before = s.character.mushrooms;
+                let silver_before = s.character.silver;
+                let souls_before =
+                    s.underworld.as_ref().map(|a| a.souls_current);
                 if let Err(e) = s.update(*resp) {
                     // it would *probably* be ok to just ignore this in most
                     // cases, but whatever
+                    let event = NotifyEvent::new(
+                        NotifyKind::FatalError,
+                        &server.ident.ident,
+                        Some(&account.name),
+                        e.to_string(),
+                    );
                     *lock = AccountStatus::FatalError(e.to_string());
-                    return Command::none();
+                    drop(lock);
+                    return self
+                        .notifier
+                        .notify(&self.config.notifications, event);
                 };
 
                 let Some(last) = &s.last_fight else {
@@ -1567,6 +1933,41 @@ impl Helper {
                     return Command::none();
                 };
 
+                info!(
+                    "{ident} {} a lure against {}",
+                    if last.has_player_won { "won" } else { "lost" },
+                    against.name
+                );
+
+                self.metrics.count_fight(
+                    &server.ident.ident,
+                    &account.name,
+                    HistoryKind::Lure,
+                    last.has_player_won,
+                    mushrooms_before.saturating_sub(s.character.mushrooms),
+                );
+                account.history.push(HistoryEntry {
+                    time: Local::now(),
+                    kind: HistoryKind::Lure,
+                    opponent: against.name.clone(),
+                    opponent_uid: against.uid,
+                    won: last.has_player_won,
+                    items: 0,
+                    mushrooms: mushrooms_before
+                        .saturating_sub(s.character.mushrooms),
+                    silver: s.character.silver.saturating_sub(silver_before),
+                    souls: s
+                        .underworld
+                        .as_ref()
+                        .zip(souls_before)
+                        .map(|(u, before)| {
+                            u.souls_current.saturating_sub(before)
+                        })
+                        .unwrap_or_default(),
+                    opponent_level: against.level,
+                    opponent_items: against.items,
+                });
+
                 si.attack_log.push((
                     Local::now(),
                     against.name,
@@ -1640,32 +2041,11 @@ impl Helper {
                 };
                 if let CrawlingStatus::Crawling { que, .. } = &server.crawling {
                     let mut que = que.lock().unwrap();
-                    que.min_level = min.max(1);
-                    que.max_level = max.max(min).min(9999);
-
+                    que.set_level_range(min, max);
                     debug!(
                         "Changed MinMax to {}/{}",
                         que.min_level, que.max_level
                     );
-                    let mut to_remove = IntSet::default();
-                    for (lvl, _) in
-                        que.lvl_skipped_accounts.range(0..que.min_level)
-                    {
-                        to_remove.insert(*lvl);
-                    }
-                    for (lvl, _) in
-                        que.lvl_skipped_accounts.range(que.max_level + 1..)
-                    {
-                        to_remove.insert(*lvl);
-                    }
-                    for lvl in to_remove {
-                        let Some(mut todo) =
-                            que.lvl_skipped_accounts.remove(&lvl)
-                        else {
-                            continue;
-                        };
-                        que.todo_accounts.append(&mut todo);
-                    }
                 }
             }
             Message::ShowClasses(val) => {
@@ -1676,44 +2056,116 @@ impl Helper {
                 let Some(cli) = &mut self.cli_crawling else {
                     return Command::none();
                 };
-                let pb = cli.mbp.add(ProgressBar::new_spinner());
 
                 let Some(url) = cli.todo_servers.pop() else {
                     cli.active -= 1;
                     if cli.active == 0 {
-                        pb.println("Finished Crawling all servers");
-                        pb.finish_and_clear();
-                        std::process::exit(0);
+                        let msg = match cli.timed_out {
+                            true => "Time budget used up",
+                            false => "Finished Crawling all servers",
+                        };
+                        return self.finish_cli_run(msg);
                     }
-                    pb.finish_and_clear();
                     return Command::none();
                 };
-                let threads = cli.threads;
-                return match self.force_init_crawling(&url, threads, pb.clone())
-                {
-                    Some(s) => s,
-                    None => {
-                        pb.println(format!(
-                            "Could not init crawling on: {url}"
-                        ));
-                        pb.finish_and_clear();
-                        return Command::perform(async {}, |_| {
-                            Message::NextCLICrawling
-                        });
-                    }
+
+                if cli.options.resume {
+                    let ident = ServerIdent::new(&url).ident;
+                    let path = cli.options.backup_path(&ident);
+                    let naked_rules = self.config.naked_rules(&ident).clone();
+                    return Command::perform(
+                        async move {
+                            let Ok(backup) = ZHofBackup::read_path(path).await
+                            else {
+                                return (false, None);
+                            };
+                            if backup.todo_pages.is_empty()
+                                && backup.todo_accounts.is_empty()
+                            {
+                                return (true, None);
+                            }
+                            let status = restore_backup(
+                                Some(Box::new(backup)),
+                                0,
+                                naked_rules,
+                            )
+                            .await;
+                            (false, Some(Box::new(status)))
+                        },
+                        move |(complete, status)| Message::CLICrawlResume {
+                            url,
+                            complete,
+                            status,
+                        },
+                    );
+                }
+                return self.start_cli_crawl(&url, None);
+            }
+            Message::CrawlJobDue(idx) => {
+                return self.crawl_job_due(idx);
+            }
+            Message::ShutdownRequested => return self.request_shutdown(),
+            Message::ShutdownWait(checks) => return self.shutdown_wait(checks),
+            Message::ShutdownBackupWritten { error } => {
+                return self.shutdown_backup_written(error);
+            }
+            Message::CLICrawlResume {
+                url,
+                complete: true,
+                ..
+            } => {
+                // Crawling again would replace the complete backup with a
+                // new, partial one
+                let Some(cli) = &mut self.cli_crawling else {
+                    return Command::none();
+                };
+                info!("Skipping {url}, because its backup is already complete");
+                let ident = ServerIdent::new(&url);
+                let progress =
+                    cli.server_done(ident.id, ServerResult::Complete);
+                cli.emit(&CrawlEvent::finished(
+                    &ident.ident,
+                    ServerResult::Complete,
+                    &progress,
+                ));
+                return Command::perform(async {}, |_| {
+                    Message::NextCLICrawling
+                });
+            }
+            Message::CLICrawlResume { url, status, .. } => {
+                return self.start_cli_crawl(&url, status.map(|a| *a));
+            }
+            Message::CLICrawlTimeout(run) => {
+                let Some(cli) = &mut self.cli_crawling else {
+                    return Command::none();
                 };
+                if cli.run != run {
+                    return Command::none();
+                }
+                cli.timed_out = true;
+                return self.stop_cli_run();
             }
-            Message::CrawlAllRes {
+            Message::CrawlServersRes {
                 servers,
                 concurrency,
             } => {
                 let Some(cli) = &mut self.cli_crawling else {
                     return Command::none();
                 };
-                let Some(servers) = servers else {
-                    _ = cli.mbp.println("Could not fetch server list");
-                    std::process::exit(1);
+                let servers = match servers {
+                    Ok(servers) => servers,
+                    Err(e) => {
+                        cli.emit(&CrawlEvent::Error {
+                            server: "",
+                            kind: "select",
+                            error: &e,
+                        });
+                        return self.finish_cli_run(&e);
+                    }
                 };
+                if cli.timed_out || cli.interrupted {
+                    return self.finish_cli_run("Crawling was stopped");
+                }
                 cli.todo_servers = servers;
                 let mut res = vec![];
                 for _ in 0..concurrency {
@@ -1790,6 +2242,62 @@ impl Helper {
                 self.config.blacklist_threshold = nv.max(1);
                 _ = self.config.write();
             }
+            Message::SetBlacklistExpiry(nv) => {
+                self.config.blacklist_expiry_days = nv;
+                _ = self.config.write();
+                return self.update_all_best();
+            }
+            Message::PlayerBlacklistAdd { ident, uid, name } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.blacklist
+                    .entry(uid)
+                    .or_insert(BlacklistEntry {
+                        uid,
+                        name,
+                        losses: 0,
+                        last_loss: None,
+                        manual: false,
+                    })
+                    .manual = true;
+                self.persist_blacklist(ident);
+                return self.update_best(ident, false);
+            }
+            Message::PlayerBlacklistRemove { ident, uid } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.blacklist.remove(&uid);
+                self.persist_blacklist(ident);
+                return self.update_best(ident, false);
+            }
+            Message::SetEpicPolicy(nv) => {
+                self.config.epic_policy = nv;
+                _ = self.config.write();
+                return self.update_all_best();
+            }
+            Message::SetEpicWeight(nv) => {
+                self.config.epic_weight = nv.max(1);
+                _ = self.config.write();
+                return self.update_all_best();
+            }
             Message::AutoLureIdle => {}
             Message::AutoLurePossible { ident } => {
                 let refetch = self.update_best(ident, true);
@@ -1827,7 +2335,8 @@ impl Helper {
                 };
 
                 let total_len = ui.best.len();
-                let new_len = ui.best.iter().filter(|a| !a.is_old()).count();
+                let new_len =
+                    ui.best.iter().filter(|a| !a.info.is_old()).count();
 
                 // Thelist will be mostly old at startup.
                 // Therefore, we should wait until the list is mostly fetched,
@@ -1839,8 +2348,13 @@ impl Helper {
                     return refetch;
                 }
 
-                let Some(target) =
-                    ui.best.iter().find(|a| !a.is_old()).cloned()
+                // The list is sorted by the expected reward, so the first
+                // entry is the best target, that we have not lured today
+                let Some(target) = ui
+                    .best
+                    .iter()
+                    .find(|a| !a.info.is_old())
+                    .map(|a| a.info.clone())
                 else {
                     status.put_session(session);
                     return refetch;
@@ -1862,6 +2376,8 @@ impl Helper {
                             against: LureTarget {
                                 uid: target.uid,
                                 name: target.name,
+                                level: target.level,
+                                items: target.equipment.len(),
                             },
                             resp: Box::new(resp),
                         },
@@ -1919,19 +2435,373 @@ impl Helper {
                 );
 
                 for a in &si.best {
-                    if a.is_old() {
+                    if a.info.is_old() {
                         continue;
                     }
                     _ = res.write_fmt(format_args!(
-                        "lvl: {:3}, items: {}, name: {}\n",
-                        a.level,
-                        a.equipment.len(),
-                        a.name,
+                        "lvl: {:3}, items: {}, chance: {:3.0}%, name: {}\n",
+                        a.info.level,
+                        a.info.equipment.len(),
+                        a.success * 100.0,
+                        a.info.name,
                     ));
                 }
 
                 return iced::clipboard::write(res);
             }
+            Message::SetMushroomPolicy { ident, policy } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.mushroom_policy = policy;
+                self.persist_automation(ident);
+            }
+            Message::SetBattleLimits { ident, limits } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.limits = limits;
+                self.persist_automation(ident);
+            }
+            Message::SetNakedLimits { server, limits } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                let config = &self.config;
+                server
+                    .naked_draft
+                    .get_or_insert_with(|| {
+                        config.naked_rules(&server.ident.ident).clone()
+                    })
+                    .limits = limits;
+            }
+            Message::SetNakedSlot {
+                server,
+                slot,
+                empty,
+            } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                let config = &self.config;
+                let rules = server.naked_draft.get_or_insert_with(|| {
+                    config.naked_rules(&server.ident.ident).clone()
+                });
+                rules.empty_slots.retain(|a| *a != slot);
+                if empty {
+                    rules.empty_slots.push(slot);
+                    rules.empty_slots.sort_by_key(|a| a.raw_id());
+                }
+            }
+            Message::ApplyNakedRules { server } => {
+                let Some(info) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                let Some(rules) = info.naked_draft.take() else {
+                    return Command::none();
+                };
+                self.config
+                    .naked_rules
+                    .insert(info.ident.ident.clone(), rules);
+                return self.apply_naked_rules(server);
+            }
+            Message::SetSchedule {
+                ident,
+                kind,
+                schedule,
+            } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let current = match kind {
+                    ScheduleKind::Battle => {
+                        account.scrapbook_info.as_mut().map(|a| &mut a.schedule)
+                    }
+                    ScheduleKind::Lure => account
+                        .underworld_info
+                        .as_mut()
+                        .map(|a| &mut a.schedule),
+                };
+                let Some(current) = current else {
+                    return Command::none();
+                };
+                *current = schedule;
+                self.persist_automation(ident);
+            }
+            Message::SetNotifyEvent { kind, enabled } => {
+                self.config.notifications.set_enabled(kind, enabled);
+                _ = self.config.write();
+            }
+            Message::SetNotifyCooldown(nv) => {
+                self.config.notifications.cooldown_minutes = nv;
+                _ = self.config.write();
+            }
+            Message::WebhookInput(url) => {
+                self.new_webhook = url;
+            }
+            Message::WebhookAdd => {
+                let url = self.new_webhook.trim().to_string();
+                if url.is_empty() {
+                    return Command::none();
+                }
+                self.new_webhook.clear();
+                self.config.notifications.webhooks.push(Webhook {
+                    url,
+                    format: WebhookFormat::default(),
+                });
+                _ = self.config.write();
+            }
+            Message::WebhookRemove(idx) => {
+                let webhooks = &mut self.config.notifications.webhooks;
+                if idx < webhooks.len() {
+                    webhooks.remove(idx);
+                    _ = self.config.write();
+                }
+            }
+            Message::WebhookSetFormat { idx, format } => {
+                let Some(webhook) =
+                    self.config.notifications.webhooks.get_mut(idx)
+                else {
+                    return Command::none();
+                };
+                webhook.format = format;
+                _ = self.config.write();
+            }
+            Message::WebhookTest => {
+                let webhooks = self.config.notifications.webhooks.clone();
+                let event = NotifyEvent::new(
+                    NotifyKind::Test,
+                    "Helper",
+                    None,
+                    "This is a test notification",
+                );
+                return Command::perform(send_event(webhooks, event), |res| {
+                    Message::WebhookSent { error: res.err() }
+                });
+            }
+            Message::ApiRequest { request, conn } => {
+                debug!("API {} /{}", request.method, request.path.join("/"));
+                let token = request.token.as_deref();
+                if !token_matches(&self.config.api.token, token) {
+                    let body = serde_json::json!({ "error": "invalid token" });
+                    return conn.respond(401, body);
+                }
+                if request.path == ["metrics"] && self.config.api.metrics {
+                    let body = self.metrics.render(&self.servers);
+                    return conn
+                        .respond_text(200, "text/plain; version=0.0.4", body);
+                }
+                let (status, body, command) = self.handle_api(request);
+                return Command::batch([command, conn.respond(status, body)]);
+            }
+            Message::ApiResponded => {}
+            Message::SetApiEnabled(enabled) => {
+                self.config.api.enabled = enabled;
+                if enabled && self.config.api.token.is_empty() {
+                    self.config.api.token = generate_token();
+                }
+                _ = self.config.write();
+            }
+            Message::SetMetricsEnabled(enabled) => {
+                self.config.api.metrics = enabled;
+                if enabled && self.config.api.token.is_empty() {
+                    self.config.api.token = generate_token();
+                }
+                _ = self.config.write();
+            }
+            Message::SetApiPort(port) => {
+                self.config.api.port = port;
+                _ = self.config.write();
+            }
+            Message::ApiBindInput(bind) => self.api_bind = bind,
+            Message::SetApiBind => {
+                match self.api_bind.trim().parse() {
+                    Ok(bind) => {
+                        self.config.api.bind = bind;
+                        _ = self.config.write();
+                    }
+                    Err(_) => warn!("Invalid API address: {}", self.api_bind),
+                }
+                self.api_bind.clear();
+            }
+            Message::SetApiToken(token) => {
+                self.config.api.token = token;
+                _ = self.config.write();
+            }
+            Message::GenerateApiToken => {
+                self.config.api.token = generate_token();
+                _ = self.config.write();
+            }
+            Message::MasterPasswordInput(password) => {
+                self.master_password = password;
+            }
+            Message::UnlockConfig => {
+                let Some(header) = self.config.encryption.clone() else {
+                    return Command::none();
+                };
+                if self.deriving_key {
+                    return Command::none();
+                }
+                self.deriving_key = true;
+                let password = std::mem::take(&mut self.master_password);
+                // Deriving the key takes a while, so this must not block the
+                // UI
+                return Command::perform(
+                    tokio::task::spawn_blocking(move || {
+                        Vault::unlock(&password, &header)
+                    }),
+                    |res| {
+                        Message::ConfigUnlocked(match res {
+                            Ok(res) => res.map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        })
+                    },
+                );
+            }
+            Message::ConfigUnlocked(res) => {
+                self.deriving_key = false;
+                if let Err(e) = res.and_then(|vault| {
+                    self.config.use_vault(vault).map_err(|e| e.to_string())
+                }) {
+                    self.master_password_status = Some(e);
+                    return Command::none();
+                }
+                self.master_password_status = None;
+                self.current_view = View::Login;
+                return self.auto_login();
+            }
+            Message::SetMasterPassword => {
+                if self.master_password.is_empty()
+                    || self.config.is_locked()
+                    || self.deriving_key
+                {
+                    return Command::none();
+                }
+                self.deriving_key = true;
+                let password = std::mem::take(&mut self.master_password);
+                return Command::perform(
+                    tokio::task::spawn_blocking(move || {
+                        Vault::create(&password)
+                    }),
+                    |res| {
+                        Message::MasterPasswordCreated(match res {
+                            Ok(res) => res.map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        })
+                    },
+                );
+            }
+            Message::MasterPasswordCreated(res) => {
+                self.deriving_key = false;
+                let res = res.and_then(|(vault, header)| {
+                    self.config
+                        .set_master_password(vault, header)
+                        .map_err(|e| e.to_string())
+                });
+                self.master_password_status = Some(match res {
+                    Ok(()) => "The passwords are now encrypted".to_string(),
+                    Err(e) => format!("Could not set the password: {e}"),
+                });
+            }
+            Message::ReloadConfig => match Config::restore(&self.config.path) {
+                Ok(config) => {
+                    self.config = config;
+                    return self.config_loaded();
+                }
+                Err(e) => {
+                    self.current_view = View::InvalidConfig {
+                        error: e.to_string(),
+                    };
+                }
+            },
+            Message::ResetConfig => {
+                let path = self.config.path.clone();
+                let invalid = config::with_suffix(&path, ".invalid");
+                if let Err(e) = std::fs::rename(&path, &invalid) {
+                    self.current_view = View::InvalidConfig {
+                        error: format!("Could not move the config: {e}"),
+                    };
+                    return Command::none();
+                }
+                warn!("Moved the invalid config to {}", invalid.display());
+                self.config = Config {
+                    path,
+                    ..Default::default()
+                };
+                _ = self.config.write();
+                return self.config_loaded();
+            }
+            Message::SwitchProfile(name) => return self.switch_profile(name),
+            Message::ProfileNameInput(name) => self.profiles.new_name = name,
+            Message::CreateProfile => self.create_profile(),
+            Message::RemoveMasterPassword => {
+                self.config.remove_master_password();
+                self.master_password_status =
+                    Some("The passwords are no longer encrypted".to_string());
+            }
+            Message::WebhookSent { error } => {
+                self.webhook_status = Some(match error {
+                    Some(e) => format!("Failed: {e}"),
+                    None => "Sent successfully".to_string(),
+                });
+            }
+            Message::SetHistoryFilter(filter) => {
+                self.history_filter = filter;
+            }
+            Message::SetHistoryDays(days) => {
+                self.history_filter.days = days;
+            }
+            Message::ExportHistory { ident } => {
+                let Some((_, account)) = self.servers.get_ident(&ident) else {
+                    return Command::none();
+                };
+                let Some(path) = account.history.export_path() else {
+                    self.history_export =
+                        Some("The history has not been loaded yet".to_string());
+                    return Command::none();
+                };
+                let csv = account.history.to_csv();
+                return Command::perform(
+                    async move {
+                        tokio::fs::write(&path, csv)
+                            .await
+                            .map(|_| path.display().to_string())
+                    },
+                    |res| Message::HistoryExported {
+                        result: res.map_err(|a| a.to_string()),
+                    },
+                );
+            }
+            Message::HistoryExported { result } => {
+                self.history_export = Some(match result {
+                    Ok(path) => format!("Exported to {path}"),
+                    Err(e) => {
+                        warn!("Could not export history: {e}");
+                        format!("Export failed: {e}")
+                    }
+                });
+            }
             Message::SetAction(a) => {
                 let View::Overview { action, .. } = &mut self.current_view
                 else {
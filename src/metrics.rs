@@ -0,0 +1,271 @@
+use std::{collections::BTreeMap, fmt::Write, time::Instant};
+
+use crate::{
+    crawler::CrawlerError,
+    history::HistoryKind,
+    player::AccountStatus,
+    server::{CrawlingStatus, Servers},
+};
+
+/// The server ident & character name, that a metric belongs to
+type CharLabel = (String, String);
+
+/// Counts everything, that has happened since the helper was started, so that
+/// it can be exported in the Prometheus text format. These are served at
+/// `/metrics` on the address of the API and require its token, so the scrape
+/// config needs `authorization: { credentials: <token> }`
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    pages: BTreeMap<String, u64>,
+    characters: BTreeMap<String, u64>,
+    crawl_errors: BTreeMap<(String, &'static str), u64>,
+    fights: BTreeMap<(CharLabel, &'static str, bool), u64>,
+    mushrooms: BTreeMap<CharLabel, u64>,
+    relogins: BTreeMap<CharLabel, u64>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            pages: Default::default(),
+            characters: Default::default(),
+            crawl_errors: Default::default(),
+            fights: Default::default(),
+            mushrooms: Default::default(),
+            relogins: Default::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn count_page(&mut self, server: &str) {
+        *self.pages.entry(server.to_string()).or_default() += 1;
+    }
+
+    pub fn count_character(&mut self, server: &str) {
+        *self.characters.entry(server.to_string()).or_default() += 1;
+    }
+
+    pub fn count_crawl_error(&mut self, server: &str, error: &CrawlerError) {
+        *self
+            .crawl_errors
+            .entry((server.to_string(), error.kind()))
+            .or_default() += 1;
+    }
+
+    pub fn count_fight(
+        &mut self,
+        server: &str,
+        name: &str,
+        kind: HistoryKind,
+        won: bool,
+        mushrooms: u32,
+    ) {
+        let label = (server.to_string(), name.to_string());
+        let kind = match kind {
+            HistoryKind::Fight => "fight",
+            HistoryKind::Lure => "lure",
+        };
+        *self.fights.entry((label.clone(), kind, won)).or_default() += 1;
+        *self.mushrooms.entry(label).or_default() += u64::from(mushrooms);
+    }
+
+    pub fn count_relogin(&mut self, server: &str, name: &str) {
+        *self
+            .relogins
+            .entry((server.to_string(), name.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Renders all metrics in the Prometheus text format
+    pub fn render(&self, servers: &Servers) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "build_info", "gauge", "The version of the helper");
+        _ = writeln!(
+            out,
+            "sf_helper_build_info{{version=\"{}\"}} 1",
+            env!("CARGO_PKG_VERSION")
+        );
+        header(
+            &mut out, "uptime_seconds", "gauge",
+            "Seconds since the helper was started",
+        );
+        _ = writeln!(
+            out,
+            "sf_helper_uptime_seconds {}",
+            self.started.elapsed().as_secs()
+        );
+
+        header(
+            &mut out, "crawled_pages_total", "counter", "HoF pages crawled",
+        );
+        for (server, count) in &self.pages {
+            _ = writeln!(
+                out,
+                "sf_helper_crawled_pages_total{{server=\"{}\"}} {count}",
+                escape(server)
+            );
+        }
+        header(
+            &mut out, "crawled_characters_total", "counter",
+            "Characters crawled",
+        );
+        for (server, count) in &self.characters {
+            _ = writeln!(
+                out,
+                "sf_helper_crawled_characters_total{{server=\"{}\"}} {count}",
+                escape(server)
+            );
+        }
+        header(
+            &mut out, "crawl_errors_total", "counter",
+            "Failed crawler requests",
+        );
+        for ((server, kind), count) in &self.crawl_errors {
+            let label = format!("server=\"{}\"", escape(server));
+            _ = writeln!(
+                out,
+                "sf_helper_crawl_errors_total{{{label},kind=\"{kind}\"}} \
+                 {count}"
+            );
+        }
+
+        header(
+            &mut out, "crawl_remaining", "gauge", "Characters left to crawl",
+        );
+        let mut known = String::new();
+        let mut threads = String::new();
+        for server in servers.0.values() {
+            let CrawlingStatus::Crawling {
+                que,
+                player_info,
+                threads: thread_count,
+                ..
+            } = &server.crawling
+            else {
+                continue;
+            };
+            let label = format!("server=\"{}\"", escape(&server.ident.ident));
+            let remaining = que.lock().unwrap().count_remaining();
+            _ = writeln!(
+                out,
+                "sf_helper_crawl_remaining{{{label}}} {remaining}"
+            );
+            _ = writeln!(
+                known,
+                "sf_helper_known_characters{{{label}}} {}",
+                player_info.len()
+            );
+            _ = writeln!(
+                threads,
+                "sf_helper_crawl_threads{{{label}}} {thread_count}"
+            );
+        }
+        header(
+            &mut out, "known_characters", "gauge", "Characters in the HoF data",
+        );
+        out.push_str(&known);
+        header(&mut out, "crawl_threads", "gauge", "Active crawler threads");
+        out.push_str(&threads);
+
+        header(
+            &mut out, "fights_total", "counter",
+            "Fights & lures done by characters",
+        );
+        for (((server, name), kind, won), count) in &self.fights {
+            let result = if *won { "won" } else { "lost" };
+            let label = format!(
+                "{},kind=\"{kind}\",result=\"{result}\"",
+                char_label(server, name)
+            );
+            _ = writeln!(out, "sf_helper_fights_total{{{label}}} {count}");
+        }
+        header(
+            &mut out, "mushrooms_spent_total", "counter",
+            "Mushrooms spent on fights & lures",
+        );
+        for ((server, name), count) in &self.mushrooms {
+            let label = char_label(server, name);
+            _ = writeln!(
+                out,
+                "sf_helper_mushrooms_spent_total{{{label}}} {count}"
+            );
+        }
+        header(
+            &mut out, "relogins_total", "counter",
+            "Attempts to log characters in again",
+        );
+        for ((server, name), count) in &self.relogins {
+            let label = char_label(server, name);
+            _ = writeln!(out, "sf_helper_relogins_total{{{label}}} {count}");
+        }
+
+        let mut up = String::new();
+        let mut scrapbook = String::new();
+        let mut mushrooms = String::new();
+        for server in servers.0.values() {
+            for account in server.accounts.values() {
+                let label = char_label(&server.ident.ident, &account.name);
+                let current = match &*account.status.lock().unwrap() {
+                    AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                        Some(gs.character.mushrooms)
+                    }
+                    _ => None,
+                };
+                _ = writeln!(
+                    up,
+                    "sf_helper_character_up{{{label}}} {}",
+                    u8::from(current.is_some())
+                );
+                if let Some(current) = current {
+                    _ = writeln!(
+                        mushrooms,
+                        "sf_helper_mushrooms{{{label}}} {current}"
+                    );
+                }
+                if let Some(si) = &account.scrapbook_info {
+                    _ = writeln!(
+                        scrapbook,
+                        "sf_helper_scrapbook_items{{{label}}} {}",
+                        si.scrapbook.items.len()
+                    );
+                }
+            }
+        }
+        header(
+            &mut out, "character_up", "gauge", "If the character is logged in",
+        );
+        out.push_str(&up);
+        header(&mut out, "mushrooms", "gauge", "Mushrooms of the character");
+        out.push_str(&mushrooms);
+        header(
+            &mut out, "scrapbook_items", "gauge",
+            "Items in the scrapbook of the character",
+        );
+        out.push_str(&scrapbook);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, typ: &str, help: &str) {
+    _ = writeln!(out, "# HELP sf_helper_{name} {help}");
+    _ = writeln!(out, "# TYPE sf_helper_{name} {typ}");
+}
+
+fn char_label(server: &str, name: &str) -> String {
+    format!(
+        "server=\"{}\",character=\"{}\"",
+        escape(server),
+        escape(name)
+    )
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use iced::Command;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::message::Message;
+
+/// The things, that we can send notifications about
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotifyKind {
+    FatalError,
+    CrawlerDied,
+    BattleStuck,
+    BattleStopped,
+    RareItem,
+    /// Sent manually from the settings
+    Test,
+}
+
+impl NotifyKind {
+    pub const ALL: [NotifyKind; 5] = [
+        NotifyKind::FatalError,
+        NotifyKind::CrawlerDied,
+        NotifyKind::BattleStuck,
+        NotifyKind::BattleStopped,
+        NotifyKind::RareItem,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            NotifyKind::FatalError => "Character error",
+            NotifyKind::CrawlerDied => "Crawler died",
+            NotifyKind::BattleStuck => "Auto-battle stuck",
+            NotifyKind::BattleStopped => "Auto-battle stopped",
+            NotifyKind::RareItem => "Rare item found",
+            NotifyKind::Test => "Test",
+        }
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            NotifyKind::FatalError => "fatal_error",
+            NotifyKind::CrawlerDied => "crawler_died",
+            NotifyKind::BattleStuck => "battle_stuck",
+            NotifyKind::BattleStopped => "battle_stopped",
+            NotifyKind::RareItem => "rare_item",
+            NotifyKind::Test => "test",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub webhooks: Vec<Webhook>,
+    pub fatal_error: bool,
+    pub crawler_died: bool,
+    pub battle_stuck: bool,
+    pub battle_stopped: bool,
+    pub rare_item: bool,
+    /// The minimum amount of minutes between two notifications of the same
+    /// kind for the same character
+    pub cooldown_minutes: u32,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: vec![],
+            fatal_error: true,
+            crawler_died: true,
+            battle_stuck: true,
+            battle_stopped: true,
+            rare_item: true,
+            cooldown_minutes: 30,
+        }
+    }
+}
+
+impl NotifyConfig {
+    pub fn is_enabled(&self, kind: NotifyKind) -> bool {
+        match kind {
+            NotifyKind::FatalError => self.fatal_error,
+            NotifyKind::CrawlerDied => self.crawler_died,
+            NotifyKind::BattleStuck => self.battle_stuck,
+            NotifyKind::BattleStopped => self.battle_stopped,
+            NotifyKind::RareItem => self.rare_item,
+            NotifyKind::Test => true,
+        }
+    }
+
+    pub fn set_enabled(&mut self, kind: NotifyKind, enabled: bool) {
+        let val = match kind {
+            NotifyKind::FatalError => &mut self.fatal_error,
+            NotifyKind::CrawlerDied => &mut self.crawler_died,
+            NotifyKind::BattleStuck => &mut self.battle_stuck,
+            NotifyKind::BattleStopped => &mut self.battle_stopped,
+            NotifyKind::RareItem => &mut self.rare_item,
+            NotifyKind::Test => return,
+        };
+        *val = enabled;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+/// The shape of the JSON body, that gets posted to a webhook
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq,
+)]
+pub enum WebhookFormat {
+    #[default]
+    Discord,
+    Slack,
+    Generic,
+}
+
+#[allow(clippy::to_string_trait_impl)]
+impl ToString for WebhookFormat {
+    fn to_string(&self) -> String {
+        match self {
+            WebhookFormat::Discord => "Discord",
+            WebhookFormat::Slack => "Slack",
+            WebhookFormat::Generic => "Generic",
+        }
+        .to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotifyEvent {
+    pub kind: NotifyKind,
+    pub server: String,
+    pub character: Option<String>,
+    pub message: String,
+}
+
+impl NotifyEvent {
+    pub fn new(
+        kind: NotifyKind,
+        server: &str,
+        character: Option<&str>,
+        message: impl Into<String>,
+    ) -> NotifyEvent {
+        NotifyEvent {
+            kind,
+            server: server.to_string(),
+            character: character.map(|a| a.to_string()),
+            message: message.into(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let source = match &self.character {
+            Some(name) => format!("{name} ({})", self.server),
+            None => self.server.clone(),
+        };
+        format!("[{}] {source}: {}", self.kind.title(), self.message)
+    }
+
+    fn payload(&self, format: WebhookFormat) -> serde_json::Value {
+        match format {
+            WebhookFormat::Discord => {
+                serde_json::json!({ "content": self.text() })
+            }
+            WebhookFormat::Slack => serde_json::json!({ "text": self.text() }),
+            WebhookFormat::Generic => serde_json::json!({
+                "event": self.kind.id(),
+                "server": self.server,
+                "character": self.character,
+                "message": self.message,
+                "time": Local::now().to_rfc3339(),
+            }),
+        }
+    }
+}
+
+/// Remembers when we last sent which notification, so that we do not spam the
+/// webhooks, if something goes wrong repeatedly
+#[derive(Debug, Default)]
+pub struct Notifier {
+    last_sent: HashMap<String, Instant, ahash::RandomState>,
+}
+
+impl Notifier {
+    /// Sends the event to all configured webhooks, unless this kind of event
+    /// is disabled, or has just been sent
+    pub fn notify(
+        &mut self,
+        config: &NotifyConfig,
+        event: NotifyEvent,
+    ) -> Command<Message> {
+        if config.webhooks.is_empty()
+            || !config.is_enabled(event.kind)
+            || !self.should_send(&event, config.cooldown_minutes)
+        {
+            return Command::none();
+        }
+        let webhooks = config.webhooks.clone();
+        Command::perform(send_event(webhooks, event), |res| {
+            Message::WebhookSent { error: res.err() }
+        })
+    }
+
+    fn should_send(&mut self, event: &NotifyEvent, cooldown: u32) -> bool {
+        let key = format!(
+            "{}/{}/{}",
+            event.kind.id(),
+            event.server,
+            event.character.as_deref().unwrap_or_default()
+        );
+        let cooldown = Duration::from_secs(u64::from(cooldown) * 60);
+        let now = Instant::now();
+        if let Some(last) = self.last_sent.get(&key) {
+            if now.duration_since(*last) < cooldown {
+                return false;
+            }
+        }
+        self.last_sent.insert(key, now);
+        true
+    }
+}
+
+pub async fn send_event(
+    webhooks: Vec<Webhook>,
+    event: NotifyEvent,
+) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let mut errors = vec![];
+    for webhook in webhooks {
+        let body = event.payload(webhook.format).to_string();
+        let res = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|a| a.error_for_status());
+        if let Err(e) = res {
+            warn!("Could not send notification to {}: {e}", webhook.url);
+            errors.push(e.to_string());
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Accepts `count` requests and returns the path & JSON body of each
+    fn receiver(count: usize) -> (String, mpsc::Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&mut stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((key, value)) = line.split_once(':') {
+                        if key.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                    .unwrap();
+                tx.send((path, serde_json::from_slice(&body).unwrap()))
+                    .unwrap();
+            }
+        });
+        (format!("http://{addr}"), rx)
+    }
+
+    fn event() -> NotifyEvent {
+        NotifyEvent::new(
+            NotifyKind::BattleStuck,
+            "s1.sfgame.net",
+            Some("hero"),
+            "No fight since 12:00",
+        )
+    }
+
+    #[test]
+    fn webhook_payloads() {
+        let (base, rx) = receiver(3);
+        let webhooks = [
+            ("discord", WebhookFormat::Discord),
+            ("slack", WebhookFormat::Slack),
+            ("generic", WebhookFormat::Generic),
+        ]
+        .map(|(path, format)| Webhook {
+            url: format!("{base}/{path}"),
+            format,
+        })
+        .to_vec();
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(send_event(webhooks, event())).unwrap();
+
+        let mut bodies: HashMap<String, Value> = rx.iter().take(3).collect();
+        let text = "[Auto-battle stuck] hero (s1.sfgame.net): No fight since \
+                    12:00";
+        assert_eq!(bodies["/discord"], serde_json::json!({ "content": text }));
+        assert_eq!(bodies["/slack"], serde_json::json!({ "text": text }));
+
+        let generic = bodies.remove("/generic").unwrap();
+        assert_eq!(generic["event"], "battle_stuck");
+        assert_eq!(generic["server"], "s1.sfgame.net");
+        assert_eq!(generic["character"], "hero");
+        assert_eq!(generic["message"], "No fight since 12:00");
+        assert!(generic["time"].is_string());
+    }
+
+    #[test]
+    fn cooldown() {
+        let mut notifier = Notifier::default();
+        assert!(notifier.should_send(&event(), 30));
+        assert!(!notifier.should_send(&event(), 30));
+
+        // Other characters & kinds have their own cooldown
+        let mut other = event();
+        other.character = Some("sidekick".to_string());
+        assert!(notifier.should_send(&other, 30));
+        let mut other = event();
+        other.kind = NotifyKind::RareItem;
+        assert!(notifier.should_send(&other, 30));
+
+        // Without a cooldown, everything is sent
+        assert!(notifier.should_send(&event(), 0));
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 
 use chrono::{DateTime, Local};
 use log::trace;
-use nohash_hasher::IntMap;
+use nohash_hasher::{IntMap, IntSet};
 use sf_api::{
     gamestate::{underworld::Underworld, unlockables::ScrapBook, GameState},
     session::Session,
@@ -13,8 +13,15 @@ use sf_api::{
 use tokio::time::sleep;
 
 use crate::{
-    config::CharacterConfig, login::PlayerAuth, message::Message, AccountIdent,
-    AttackTarget, CharacterInfo,
+    completion::ScrapbookCompletion,
+    config::{BattleLimits, BlacklistEntry, CharacterConfig, MushroomPolicy},
+    history::History,
+    login::PlayerAuth,
+    lure::LureCandidate,
+    message::Message,
+    schedule::{Schedule, ScheduleKind},
+    server::ServerIdent,
+    AccountIdent, AttackTarget,
 };
 
 pub struct AccountInfo {
@@ -25,14 +32,16 @@ pub struct AccountInfo {
     pub status: Arc<Mutex<AccountStatus>>,
     pub scrapbook_info: Option<ScrapbookInfo>,
     pub underworld_info: Option<UnderworldInfo>,
+    pub history: History,
 }
 
 pub struct UnderworldInfo {
     pub underworld: Underworld,
-    pub best: Vec<CharacterInfo>,
+    pub best: Vec<LureCandidate>,
     pub max_level: u16,
     pub attack_log: Vec<(DateTime<Local>, String, bool)>,
     pub auto_lure: bool,
+    pub schedule: Schedule,
 }
 
 impl UnderworldInfo {
@@ -41,31 +50,53 @@ impl UnderworldInfo {
         config: Option<&CharacterConfig>,
     ) -> Option<Self> {
         let underworld = gs.underworld.as_ref()?.clone();
-        let avg_lvl = underworld
-            .units
-            .as_array()
-            .iter()
-            .map(|a| a.level as u64)
-            .sum::<u64>() as f32
-            / 3.0;
+        let avg_lvl = avg_unit_level(&underworld);
         Some(Self {
             underworld,
             best: Default::default(),
             max_level: avg_lvl as u16 + 20,
             attack_log: Vec::new(),
             auto_lure: config.map(|a| a.auto_lure).unwrap_or(false),
+            schedule: config.map(|a| a.lure_schedule).unwrap_or_default(),
         })
     }
 }
 
+/// After this many minutes without a fight, auto-battle is considered stuck
+pub const BATTLE_STUCK_MINUTES: i64 = 30;
+
+pub fn avg_unit_level(underworld: &Underworld) -> f32 {
+    underworld
+        .units
+        .as_array()
+        .iter()
+        .map(|a| a.level as u64)
+        .sum::<u64>() as f32
+        / 3.0
+}
+
 pub struct ScrapbookInfo {
     pub scrapbook: ScrapBook,
     pub best: Vec<AttackTarget>,
     pub max_level: u16,
     pub max_attributes: u32,
-    pub blacklist: IntMap<u32, (String, usize)>,
+    pub blacklist: IntMap<u32, BlacklistEntry>,
     pub attack_log: Vec<(DateTime<Local>, AttackTarget, bool)>,
     pub auto_battle: bool,
+    pub mushroom_policy: MushroomPolicy,
+    pub limits: BattleLimits,
+    /// The amount of fights auto-battle has lost in a row since it has been
+    /// enabled
+    pub consecutive_losses: u32,
+    /// Why auto-battle has been disabled automatically
+    pub stop_reason: Option<String>,
+    /// Since when auto-battle has been trying to fight without success
+    pub waiting_since: Option<DateTime<Local>>,
+    pub schedule: Schedule,
+    pub completion: ScrapbookCompletion,
+    /// Targets, that one of our other characters on this server is going to
+    /// attack next
+    pub claimed: IntSet<u32>,
 }
 
 impl ScrapbookInfo {
@@ -81,15 +112,46 @@ impl ScrapbookInfo {
             (total as f32 * expected_battle_luck) as u32
         };
 
-        Some(Self {
-            scrapbook: gs.character.scrapbook.as_ref()?.clone(),
+        let scrapbook = gs.character.scrapbook.as_ref()?.clone();
+        let mut res =
+            Self::from_scrapbook(scrapbook, gs.character.level, max_attributes);
+        if let Some(config) = config {
+            res.blacklist = config
+                .blacklist
+                .iter()
+                .map(|b| (b.uid, b.clone()))
+                .collect();
+            res.auto_battle = config.auto_battle;
+            res.mushroom_policy = config.mushrooms;
+            res.limits = config.battle_limits;
+            res.schedule = config.battle_schedule;
+        }
+        Some(res)
+    }
+
+    /// The info for a scrapbook, that is not backed by a logged in character
+    pub fn from_scrapbook(
+        scrapbook: ScrapBook,
+        max_level: u16,
+        max_attributes: u32,
+    ) -> Self {
+        Self {
+            scrapbook,
             best: Default::default(),
-            max_level: gs.character.level,
+            max_level,
             max_attributes,
             blacklist: Default::default(),
             attack_log: Default::default(),
-            auto_battle: config.map(|a| a.auto_battle).unwrap_or(false),
-        })
+            auto_battle: false,
+            mushroom_policy: Default::default(),
+            limits: Default::default(),
+            consecutive_losses: 0,
+            stop_reason: None,
+            waiting_since: None,
+            schedule: Default::default(),
+            completion: Default::default(),
+            claimed: Default::default(),
+        }
     }
 }
 
@@ -104,6 +166,7 @@ impl AccountInfo {
             auth,
             scrapbook_info: None,
             underworld_info: None,
+            history: Default::default(),
             last_updated: Local::now(),
             status: Arc::new(Mutex::new(AccountStatus::LoggingIn)),
             ident,
@@ -111,6 +174,65 @@ impl AccountInfo {
     }
 }
 
+impl AccountInfo {
+    /// Checks if auto-battle is allowed to spend a mushroom on its next fight,
+    /// given the current amount of mushrooms of this character
+    pub fn may_use_mushroom(&self, mushrooms: u32) -> bool {
+        let Some(si) = &self.scrapbook_info else {
+            return false;
+        };
+        si.mushroom_policy
+            .allows(self.history.mushrooms_spent_today(), mushrooms)
+    }
+
+    pub fn schedule(&self, kind: ScheduleKind) -> Option<&Schedule> {
+        match kind {
+            ScheduleKind::Battle => {
+                self.scrapbook_info.as_ref().map(|a| &a.schedule)
+            }
+            ScheduleKind::Lure => {
+                self.underworld_info.as_ref().map(|a| &a.schedule)
+            }
+        }
+    }
+
+    /// Checks if the schedule of the automation currently allows it to run
+    pub fn schedule_active(
+        &self,
+        kind: ScheduleKind,
+        server: &ServerIdent,
+    ) -> bool {
+        let Some(schedule) = self.schedule(kind) else {
+            return false;
+        };
+        if !schedule.enabled {
+            return true;
+        }
+        let server_time = match &*self.status.lock().unwrap() {
+            AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                Some(gs.server_time())
+            }
+            _ => None,
+        };
+        let now = match server_time {
+            Some(st) if schedule.server_time => st.current(),
+            _ => Local::now().naive_local(),
+        };
+        // The seed has to stay the same across restarts & versions, so that
+        // the window of a day does not move
+        let seed =
+            fnv1a(server.ident.bytes().chain([0]).chain(self.name.bytes()));
+        schedule.is_active(now, seed)
+    }
+}
+
+/// A stable 64-bit FNV-1a hash
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 pub enum AccountStatus {
     LoggingIn,
     Idle(Box<Session>, Box<GameState>),
@@ -146,6 +268,9 @@ impl AccountStatus {
 pub struct AutoAttackChecker {
     pub player_status: Arc<Mutex<AccountStatus>>,
     pub ident: AccountIdent,
+    /// Fights do not have to wait for the next free fight, because we are
+    /// allowed to spend mushrooms on them
+    pub use_mushrooms: bool,
 }
 
 impl AutoAttackChecker {
@@ -160,7 +285,13 @@ impl AutoAttackChecker {
         };
         if let Some(next) = next_fight {
             let remaining = next - Local::now();
-            if let Ok(remaining) = remaining.to_std() {
+            if let Ok(mut remaining) = remaining.to_std() {
+                if self.use_mushrooms {
+                    // We do not want to spam fights, or re-check a target,
+                    // that is not worth a mushroom every second
+                    remaining = remaining
+                        .min(Duration::from_secs(fastrand::u64(10..=20)));
+                }
                 tokio::time::sleep(remaining).await;
             }
         };
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use iced::Command;
+use log::info;
+
+use crate::{
+    config::Config, message::Message, server::CrawlingStatus, Helper, View,
+};
+
+/// The profile, that uses the config given via `--config`
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The profiles are separate configs. The default one is the main config and
+/// all others are stored in a `profiles` directory next to it
+#[derive(Debug)]
+pub struct Profiles {
+    main: PathBuf,
+    pub current: String,
+    pub available: Vec<String>,
+    pub new_name: String,
+    pub status: Option<String>,
+}
+
+impl Profiles {
+    pub fn new(main: PathBuf) -> Self {
+        let mut profiles = Profiles {
+            main,
+            current: DEFAULT_PROFILE.to_string(),
+            available: vec![],
+            new_name: String::new(),
+            status: None,
+        };
+        profiles.refresh();
+        profiles
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.main
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("profiles")
+    }
+
+    /// The config file of the profile
+    pub fn path(&self, name: &str) -> Result<PathBuf, String> {
+        if name == DEFAULT_PROFILE {
+            return Ok(self.main.clone());
+        }
+        if !self.available.iter().any(|a| a == name) {
+            return Err(format!("The profile {name} does not exist"));
+        }
+        Ok(self.dir().join(format!("{name}.toml")))
+    }
+
+    /// Reads the profiles, that exist on disk
+    fn refresh(&mut self) {
+        let mut available: Vec<_> = std::fs::read_dir(self.dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|a| a.path())
+            .filter(|a| a.extension().is_some_and(|a| a == "toml"))
+            .filter_map(|a| Some(a.file_stem()?.to_str()?.to_string()))
+            .filter(|a| is_valid_name(a) && a != DEFAULT_PROFILE)
+            .collect();
+        available.sort();
+        available.insert(0, DEFAULT_PROFILE.to_string());
+        self.available = available;
+    }
+
+    /// Creates a new profile with an empty config
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err("Profile names can only contain letters, digits, - \
+                        and _"
+                .to_string());
+        }
+        // Windows & macOS do not care about the case of file names, so "Foo"
+        // would overwrite the config of "foo"
+        if self.available.iter().any(|a| a.eq_ignore_ascii_case(name)) {
+            return Err(format!("The profile {name} already exists"));
+        }
+        std::fs::create_dir_all(self.dir()).map_err(|e| e.to_string())?;
+        let path = self.dir().join(format!("{name}.toml"));
+        // Makes sure, that we never replace a config, that we did not see
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    format!("The profile {name} already exists")
+                }
+                _ => e.to_string(),
+            })?;
+        let config = Config {
+            path,
+            ..Default::default()
+        };
+        config.write().map_err(|e| e.to_string())?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Helper {
+    /// Replaces the config with the one of another profile. The characters
+    /// of the current profile have to be logged out first. Crawlers, that
+    /// are still running with the settings & accounts of the current
+    /// profile, are stopped
+    pub fn switch_profile(&mut self, name: String) -> Command<Message> {
+        if self.has_accounts() {
+            self.profiles.status =
+                Some("Log out all characters first".to_string());
+            return Command::none();
+        }
+        let path = match self.profiles.path(&name) {
+            Ok(path) => path,
+            Err(e) => {
+                self.profiles.status = Some(e);
+                return Command::none();
+            }
+        };
+        info!("Switching to the profile {name}");
+        for server in self.servers.0.values_mut() {
+            if let CrawlingStatus::Crawling {
+                threads,
+                crawling_session,
+                ..
+            } = &mut server.crawling
+            {
+                *threads = 0;
+                *crawling_session = None;
+            }
+        }
+        self.profiles.current = name;
+        self.profiles.status = None;
+        // The SSO accounts of the last profile are not ours to use
+        self.login_state.active_sso.clear();
+        self.login_state.import_que.clear();
+        self.login_state.error = None;
+        self.master_password.clear();
+        self.master_password_status = None;
+
+        match Config::restore(&path) {
+            Ok(config) => {
+                self.config = config;
+                self.config_loaded()
+            }
+            Err(e) => {
+                self.config = Config {
+                    path,
+                    ..Default::default()
+                };
+                self.current_view = View::InvalidConfig {
+                    error: e.to_string(),
+                };
+                Command::none()
+            }
+        }
+    }
+
+    pub fn create_profile(&mut self) {
+        let name = self.profiles.new_name.trim().to_string();
+        self.profiles.status = Some(match self.profiles.create(&name) {
+            Ok(()) => {
+                self.profiles.new_name.clear();
+                format!("Created the profile {name}")
+            }
+            Err(e) => e,
+        });
+    }
+}
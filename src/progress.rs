@@ -0,0 +1,211 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// How the headless crawler reports its progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressFormat {
+    /// Progress bars on stderr
+    #[default]
+    Bars,
+    /// One JSON object per event on stdout
+    Json,
+}
+
+/// How the crawl of a single server ended
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerResult {
+    Complete,
+    /// The time budget ran out before the server was done
+    Partial,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CrawlEvent<'a> {
+    ServerStarted {
+        server: &'a str,
+        url: &'a str,
+        resumed: bool,
+    },
+    PagesDone {
+        server: &'a str,
+        pages: usize,
+    },
+    CharactersDone {
+        server: &'a str,
+        characters: usize,
+        remaining: usize,
+    },
+    Error {
+        /// Empty, if the error is not about a single server
+        #[serde(skip_serializing_if = "str::is_empty")]
+        server: &'a str,
+        kind: &'a str,
+        error: &'a str,
+    },
+    BackupWritten {
+        server: &'a str,
+        path: &'a Path,
+    },
+    ServerFinished {
+        server: &'a str,
+        result: ServerResult,
+        pages: usize,
+        characters: usize,
+        errors: usize,
+        seconds: u64,
+    },
+    Summary {
+        #[serde(flatten)]
+        summary: &'a RunSummary,
+        timed_out: bool,
+        /// The crawl was stopped by a signal
+        interrupted: bool,
+        exit_code: i32,
+    },
+}
+
+impl<'a> CrawlEvent<'a> {
+    pub fn finished(
+        server: &'a str,
+        result: ServerResult,
+        progress: &ServerProgress,
+    ) -> Self {
+        CrawlEvent::ServerFinished {
+            server,
+            result,
+            pages: progress.pages,
+            characters: progress.characters,
+            errors: progress.errors,
+            seconds: progress.elapsed_secs(),
+        }
+    }
+}
+
+/// Prints the event, if the progress is reported as JSON
+pub fn emit(format: ProgressFormat, event: &CrawlEvent) {
+    if format != ProgressFormat::Json {
+        return;
+    }
+    if let Ok(line) = serde_json::to_string(event) {
+        println!("{line}");
+    }
+}
+
+/// What has happened on a single server in the current run
+#[derive(Debug)]
+pub struct ServerProgress {
+    pub pages: usize,
+    pub characters: usize,
+    pub errors: usize,
+    started: Instant,
+    last_report: Option<Instant>,
+}
+
+impl Default for ServerProgress {
+    fn default() -> Self {
+        Self {
+            pages: 0,
+            characters: 0,
+            errors: 0,
+            started: Instant::now(),
+            last_report: None,
+        }
+    }
+}
+
+impl ServerProgress {
+    pub fn elapsed_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Checks if enough time has passed to report the amount of characters
+    /// again. There can be hundreds of characters per second, so we do not
+    /// want to report every single one
+    pub fn should_report(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .last_report
+            .is_some_and(|a| now - a < Duration::from_secs(1))
+        {
+            return false;
+        }
+        self.last_report = Some(now);
+        true
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    pub complete: usize,
+    pub partial: usize,
+    pub failed: usize,
+    pub pages: usize,
+    pub characters: usize,
+    pub errors: usize,
+}
+
+impl RunSummary {
+    pub fn add(&mut self, result: ServerResult, progress: &ServerProgress) {
+        match result {
+            ServerResult::Complete => self.complete += 1,
+            ServerResult::Partial => self.partial += 1,
+            ServerResult::Failed => self.failed += 1,
+        }
+        self.pages += progress.pages;
+        self.characters += progress.characters;
+        self.errors += progress.errors;
+    }
+
+    /// 0 if every server was crawled completely, 1 if no server was crawled
+    /// at all and 2 if only some of them were
+    pub fn exit_code(&self) -> i32 {
+        if self.complete == 0 && self.partial == 0 {
+            1
+        } else if self.partial > 0 || self.failed > 0 {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(complete: usize, partial: usize, failed: usize) -> RunSummary {
+        RunSummary {
+            complete,
+            partial,
+            failed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn all_complete() {
+        assert_eq!(summary(1, 0, 0).exit_code(), 0);
+        assert_eq!(summary(5, 0, 0).exit_code(), 0);
+    }
+
+    #[test]
+    fn nothing_crawled() {
+        assert_eq!(summary(0, 0, 0).exit_code(), 1);
+        assert_eq!(summary(0, 0, 3).exit_code(), 1);
+    }
+
+    #[test]
+    fn partially_crawled() {
+        assert_eq!(summary(0, 1, 0).exit_code(), 2);
+        assert_eq!(summary(2, 1, 0).exit_code(), 2);
+        assert_eq!(summary(2, 0, 1).exit_code(), 2);
+        assert_eq!(summary(0, 1, 1).exit_code(), 2);
+    }
+}
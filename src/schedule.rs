@@ -0,0 +1,441 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScheduleKind {
+    Battle,
+    Lure,
+}
+
+/// The time window in which an automation is allowed to run
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Schedule {
+    /// If this is false, the automation runs around the clock
+    pub enabled: bool,
+    /// The hour of the day, at which the automation starts
+    pub start_hour: u8,
+    /// The hour of the day, at which the automation stops. If this is equal
+    /// to, or before the start, the window ends on the next day
+    pub end_hour: u8,
+    /// The days of the week (starting with monday), on which the window
+    /// starts
+    pub days: [bool; 7],
+    /// The start and stop are randomly moved by up to this many minutes. The
+    /// offset is stable for a whole day
+    pub jitter_minutes: u32,
+    /// Use the time of the server instead of the local time
+    pub server_time: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_hour: 8,
+            end_hour: 22,
+            days: [true; 7],
+            jitter_minutes: 0,
+            server_time: true,
+        }
+    }
+}
+
+impl Schedule {
+    /// Checks if the automation is allowed to run at the given time. The seed
+    /// should be unique per character, so that not all of them start at the
+    /// exact same time
+    pub fn is_active(&self, now: NaiveDateTime, seed: u64) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let today = now.date();
+        // A window, that started yesterday can still be going on, if it
+        // crosses midnight
+        [today.pred_opt(), Some(today)]
+            .into_iter()
+            .flatten()
+            .filter_map(|day| self.window(day, seed))
+            .any(|(start, end)| start <= now && now < end)
+    }
+
+    fn window(
+        &self,
+        day: NaiveDate,
+        seed: u64,
+    ) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.days[day.weekday().num_days_from_monday() as usize] {
+            return None;
+        }
+        let start = day.and_hms_opt(self.start_hour.min(23).into(), 0, 0)?;
+        let mut end = day.and_hms_opt(self.end_hour.min(23).into(), 0, 0)?;
+        if end <= start {
+            end += Duration::days(1);
+        }
+
+        let jitter = i64::from(self.jitter_minutes);
+        if jitter == 0 {
+            return Some((start, end));
+        }
+        let mut rng = fastrand::Rng::with_seed(
+            seed ^ u64::from(day.num_days_from_ce().unsigned_abs()),
+        );
+        let start = start + Duration::minutes(rng.i64(-jitter..=jitter));
+        let end = end + Duration::minutes(rng.i64(-jitter..=jitter));
+        Some((start, end))
+    }
+}
+
+/// A cron like specification of when a recurring job runs. This consists of
+/// the usual five fields (minute, hour, day of month, month, day of week).
+/// Every field can be `*`, a number, a range (`1-5`), a list of those
+/// (`1,3,5`) and have a step (`*/15`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSpec {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Like in cron, a restricted day of month & day of week match, if
+    /// either of them matches
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for CronSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return Err(format!(
+                "Expected 5 fields in the schedule '{s}', found {}",
+                fields.len()
+            ));
+        };
+        let mut weekday_mask = parse_cron_field(weekdays, 0, 7)?;
+        // Both 0 and 7 are sunday
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask |= 1;
+        }
+        Ok(CronSpec {
+            minutes: parse_cron_field(minutes, 0, 59)?,
+            hours: parse_cron_field(hours, 0, 23)?,
+            days: parse_cron_field(days, 1, 31)?,
+            months: parse_cron_field(months, 1, 12)?,
+            weekdays: weekday_mask,
+            any_day: *days == "*",
+            any_weekday: *weekdays == "*",
+        })
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid schedule field '{field}'");
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                (range, step.parse::<u32>().map_err(|_| invalid())?)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let val = range.parse().map_err(|_| invalid())?;
+                    // `5/10` means every 10th value starting from 5
+                    (val, if step > 1 { max } else { val })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for val in (start..=end).step_by(step as usize) {
+            mask |= 1 << val;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronSpec {
+    /// The next time after `now`, at which the job should run. None, if the
+    /// spec can never match (i.e. the 31st of february)
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let matches = |mask: u64, val: u32| mask & (1 << val) != 0;
+        let mut time =
+            now.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(366 * 5);
+
+        while time < limit {
+            let date = time.date();
+            if !matches(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?
+                    .and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            let day = matches(self.days, date.day());
+            let weekday =
+                matches(self.weekdays, date.weekday().num_days_from_sunday());
+            let day_matches = match (self.any_day, self.any_weekday) {
+                (true, true) => true,
+                (true, false) => weekday,
+                (false, true) => day,
+                (false, false) => day || weekday,
+            };
+            if !day_matches {
+                time = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !matches(self.hours, time.hour()) {
+                time =
+                    date.and_hms_opt(time.hour(), 0, 0)? + Duration::hours(1);
+                continue;
+            }
+            if !matches(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn bits(vals: impl IntoIterator<Item = u32>) -> u64 {
+        vals.into_iter().fold(0, |mask, val| mask | 1 << val)
+    }
+
+    #[test]
+    fn cron_fields() {
+        let cases = [
+            ("*", bits(0..=59)),
+            ("7", bits([7])),
+            ("1-5", bits(1..=5)),
+            ("*/15", bits([0, 15, 30, 45])),
+            ("5/10", bits([5, 15, 25, 35, 45, 55])),
+            ("1-10/3", bits([1, 4, 7, 10])),
+            ("1,3,10-12", bits([1, 3, 10, 11, 12])),
+            ("0,*/30", bits([0, 30])),
+        ];
+        for (field, expected) in cases {
+            assert_eq!(parse_cron_field(field, 0, 59), Ok(expected), "{field}");
+        }
+    }
+
+    #[test]
+    fn invalid_cron() {
+        let fields = ["", "60", "5-1", "*/0", "a", "1-", "-1", "1,,2", "*/x"];
+        for field in fields {
+            assert!(parse_cron_field(field, 0, 59).is_err(), "{field}");
+        }
+        // Days of the month start at 1
+        assert!(parse_cron_field("0", 1, 31).is_err());
+
+        for spec in ["* * * *", "* * * * * *", "0 24 * * *", "0 0 0 * *"] {
+            assert!(spec.parse::<CronSpec>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn cron_weekdays() {
+        // 0 and 7 are both sunday
+        let spec: CronSpec = "0 0 * * 7".parse().unwrap();
+        assert_eq!(spec.weekdays & 1, 1);
+        assert_eq!(spec, "0 0 * * 0,7".parse().unwrap());
+    }
+
+    #[test]
+    fn next_run() {
+        let cases = [
+            // Steps within the hour
+            (
+                "*/15 * * * *",
+                "2024-01-01 10:07:30",
+                Some("2024-01-01 10:15:00"),
+            ),
+            // The exact time is not "after"
+            (
+                "30 3 * * *",
+                "2024-01-01 03:30:00",
+                Some("2024-01-02 03:30:00"),
+            ),
+            // Minutes rolling over into the next hour
+            (
+                "0 * * * *",
+                "2024-01-01 10:59:00",
+                Some("2024-01-01 11:00:00"),
+            ),
+            (
+                "5-10 2 * * *",
+                "2024-01-01 02:10:00",
+                Some("2024-01-02 02:05:00"),
+            ),
+            // Hours rolling over into the next day
+            (
+                "30 3 * * *",
+                "2024-01-01 04:00:00",
+                Some("2024-01-02 03:30:00"),
+            ),
+            // Days rolling over into the next month & year
+            (
+                "0 0 1 * *",
+                "2024-01-31 12:00:00",
+                Some("2024-02-01 00:00:00"),
+            ),
+            (
+                "0 0 1 1 *",
+                "2024-12-31 23:59:00",
+                Some("2025-01-01 00:00:00"),
+            ),
+            (
+                "0 0 * 3 *",
+                "2024-11-10 00:00:00",
+                Some("2025-03-01 00:00:00"),
+            ),
+            (
+                "0 0 29 2 *",
+                "2023-03-01 00:00:00",
+                Some("2024-02-29 00:00:00"),
+            ),
+            // The 13th or any friday
+            (
+                "0 12 13 * 5",
+                "2024-01-01 00:00:00",
+                Some("2024-01-05 12:00:00"),
+            ),
+            (
+                "0 12 13 * 5",
+                "2024-01-12 13:00:00",
+                Some("2024-01-13 12:00:00"),
+            ),
+            (
+                "0 0 * * 7",
+                "2024-01-01 00:00:00",
+                Some("2024-01-07 00:00:00"),
+            ),
+            ("0 0 31 2 *", "2024-01-01 00:00:00", None),
+        ];
+        for (spec, now, expected) in cases {
+            let spec: CronSpec = spec.parse().unwrap();
+            assert_eq!(
+                spec.next_after(time(now)),
+                expected.map(time),
+                "{spec:?} after {now}"
+            );
+        }
+    }
+
+    fn daily(start_hour: u8, end_hour: u8) -> Schedule {
+        Schedule {
+            enabled: true,
+            start_hour,
+            end_hour,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn disabled_schedule() {
+        let schedule = Schedule {
+            enabled: false,
+            ..daily(8, 9)
+        };
+        assert!(schedule.is_active(time("2024-01-01 03:00:00"), 0));
+    }
+
+    #[test]
+    fn window() {
+        let schedule = daily(8, 22);
+        let cases = [
+            ("2024-01-01 07:59:00", false),
+            ("2024-01-01 08:00:00", true),
+            ("2024-01-01 15:00:00", true),
+            ("2024-01-01 21:59:00", true),
+            ("2024-01-01 22:00:00", false),
+            ("2024-01-02 02:00:00", false),
+        ];
+        for (now, expected) in cases {
+            assert_eq!(schedule.is_active(time(now), 0), expected, "{now}");
+        }
+    }
+
+    #[test]
+    fn window_across_midnight() {
+        // Only starts on mondays (2024-01-01)
+        let mut days = [false; 7];
+        days[0] = true;
+        let schedule = Schedule {
+            days,
+            ..daily(22, 6)
+        };
+        let cases = [
+            ("2024-01-01 02:00:00", false),
+            ("2024-01-01 21:59:00", false),
+            ("2024-01-01 22:00:00", true),
+            ("2024-01-01 23:59:00", true),
+            ("2024-01-02 00:00:00", true),
+            ("2024-01-02 05:59:00", true),
+            ("2024-01-02 06:00:00", false),
+            ("2024-01-02 23:00:00", false),
+        ];
+        for (now, expected) in cases {
+            assert_eq!(schedule.is_active(time(now), 0), expected, "{now}");
+        }
+        // The same start & end means the whole day
+        let schedule = daily(5, 5);
+        assert!(schedule.is_active(time("2024-01-01 04:59:00"), 0));
+        assert!(schedule.is_active(time("2024-01-01 05:00:00"), 0));
+    }
+
+    #[test]
+    fn jitter() {
+        let jitter = Duration::minutes(30);
+        let schedule = Schedule {
+            jitter_minutes: 30,
+            ..daily(8, 22)
+        };
+        let seed = 0x1234_5678;
+        let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut offsets = std::collections::HashSet::new();
+        for day in first.iter_days().take(100) {
+            let (start, end) = schedule.window(day, seed).unwrap();
+            // The same seed always gives the same window
+            assert_eq!(schedule.window(day, seed), Some((start, end)));
+
+            let planned_start = day.and_hms_opt(8, 0, 0).unwrap();
+            let planned_end = day.and_hms_opt(22, 0, 0).unwrap();
+            assert!((start - planned_start).abs() <= jitter, "{day}");
+            assert!((end - planned_end).abs() <= jitter, "{day}");
+            offsets.insert(start - planned_start);
+
+            assert!(!schedule.is_active(planned_start - jitter - jitter, seed));
+            assert!(schedule.is_active(planned_start + jitter, seed));
+            assert!(schedule.is_active(planned_end - jitter, seed));
+            assert!(!schedule.is_active(planned_end + jitter, seed));
+        }
+        // The offset changes from day to day
+        assert!(offsets.len() > 1);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{path::PathBuf, str::FromStr};
+
+use regex::Regex;
+use sf_api::sso::ServerLookup;
+
+use crate::{config::CrawlJob, server::ServerIdent};
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct ServerSelect {
+    #[clap(flatten)]
+    source: ServerSource,
+    /// Only crawl servers, whose host matches one of these patterns. A
+    /// pattern is a glob like `s*.sfgame.de`, or a regex, if it starts with
+    /// `re:`
+    #[arg(long, value_delimiter = ' ', num_args = 1..)]
+    include: Vec<ServerPattern>,
+    /// Never crawl servers, whose host matches one of these patterns
+    #[arg(long, value_delimiter = ' ', num_args = 1..)]
+    exclude: Vec<ServerPattern>,
+    /// Ignores the `crawl_exclude` list in the config when using `--all`
+    #[arg(long)]
+    no_default_exclude: bool,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+#[group(required = true, multiple = false)]
+struct ServerSource {
+    /// Fetches a list of all servers and crawls all of them, except the ones
+    /// in the `crawl_exclude` list of the config
+    #[arg(short, long)]
+    all: bool,
+    /// The list of all server urls to fetch
+    #[arg(short, long, value_delimiter = ' ', num_args = 1..)]
+    urls: Option<Vec<String>>,
+    /// A file with one server url per line. Empty lines and lines starting
+    /// with `#` are ignored
+    #[arg(short, long)]
+    file: Option<PathBuf>,
+}
+
+/// A glob, or regex, that is matched against the complete host of a server
+#[derive(Debug, Clone)]
+pub struct ServerPattern(Regex);
+
+impl FromStr for ServerPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = match s.strip_prefix("re:") {
+            Some(regex) => format!("^(?:{regex})$"),
+            None => {
+                let mut regex = String::from("^");
+                for c in s.to_ascii_lowercase().chars() {
+                    match c {
+                        '*' => regex.push_str(".*"),
+                        '?' => regex.push('.'),
+                        c => regex.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                regex.push('$');
+                regex
+            }
+        };
+        Regex::new(&regex)
+            .map(ServerPattern)
+            .map_err(|e| format!("Invalid server pattern {s}: {e}"))
+    }
+}
+
+impl ServerPattern {
+    pub fn matches(&self, url: &str) -> bool {
+        self.0.is_match(&ServerIdent::new(url).url)
+    }
+}
+
+impl ServerSelect {
+    /// The servers of a job from the config
+    pub fn from_job(job: &CrawlJob) -> Result<Self, String> {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|a| a.parse())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(ServerSelect {
+            source: ServerSource {
+                all: job.urls.is_empty() && job.file.is_none(),
+                urls: (!job.urls.is_empty()).then(|| job.urls.clone()),
+                file: job.file.clone(),
+            },
+            include: parse(&job.include)?,
+            exclude: parse(&job.exclude)?,
+            no_default_exclude: false,
+        })
+    }
+
+    /// Collects the urls of all servers, that should be crawled
+    pub async fn resolve(
+        self,
+        default_exclude: Vec<String>,
+    ) -> Result<Vec<String>, String> {
+        let mut exclude = self.exclude;
+        let servers = if let Some(urls) = self.source.urls {
+            urls
+        } else if let Some(path) = self.source.file {
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| format!("Could not read {path:?}: {e}"))?
+                .lines()
+                .map(|a| a.trim())
+                .filter(|a| !a.is_empty() && !a.starts_with('#'))
+                .map(|a| a.to_string())
+                .collect()
+        } else {
+            if !self.no_default_exclude {
+                for pattern in default_exclude {
+                    exclude.push(pattern.parse()?);
+                }
+            }
+            ServerLookup::fetch()
+                .await
+                .map_err(|_| "Could not fetch server list".to_string())?
+                .all()
+                .into_iter()
+                .map(|a| a.to_string())
+                .collect()
+        };
+
+        let servers = filter_servers(servers, &self.include, &exclude);
+        if servers.is_empty() {
+            return Err("No server matches the selection".to_string());
+        }
+        Ok(servers)
+    }
+}
+
+/// Keeps the servers, that match one of the includes (if there are any) and
+/// none of the excludes. Exclusions always win
+fn filter_servers(
+    servers: Vec<String>,
+    include: &[ServerPattern],
+    exclude: &[ServerPattern],
+) -> Vec<String> {
+    servers
+        .into_iter()
+        .filter(|url| {
+            (include.is_empty() || include.iter().any(|a| a.matches(url)))
+                && !exclude.iter().any(|a| a.matches(url))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn patterns(patterns: &[&str]) -> Vec<ServerPattern> {
+        patterns.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn pattern_matching() {
+        let cases = [
+            ("s1.sfgame.de", "https://s1.sfgame.de/", true),
+            ("s1.sfgame.de", "S1.SFGAME.DE", true),
+            ("s1.sfgame.de", "s11.sfgame.de", false),
+            ("s*.sfgame.de", "s11.sfgame.de", true),
+            ("s*.sfgame.de", "s1.sfgame.net", false),
+            ("s?.sfgame.de", "s1.sfgame.de", true),
+            ("s?.sfgame.de", "s11.sfgame.de", false),
+            // The dot is not a wildcard in globs
+            ("s1.sfgame.de", "s1xsfgame.de", false),
+            ("re:s[0-9]+\\.sfgame\\.de", "https://s42.sfgame.de/", true),
+            ("re:s[0-9]+\\.sfgame\\.de", "w42.sfgame.de", false),
+            // Regexes always have to match the complete host
+            ("re:sfgame", "s1.sfgame.de", false),
+            ("re:.*sfgame.*", "s1.sfgame.de", true),
+        ];
+        for (pattern, url, expected) in cases {
+            let parsed: ServerPattern = pattern.parse().unwrap();
+            assert_eq!(parsed.matches(url), expected, "{pattern} {url}");
+        }
+        assert!("re:(".parse::<ServerPattern>().is_err());
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let servers = urls(&[
+            "s1.sfgame.de", "s2.sfgame.de", "s1.sfgame.net", "w1.sfgame.net",
+        ]);
+        let cases: [(&[&str], &[&str], &[&str]); 5] = [
+            (
+                &[],
+                &[],
+                &[
+                    "s1.sfgame.de", "s2.sfgame.de", "s1.sfgame.net",
+                    "w1.sfgame.net",
+                ],
+            ),
+            (&["*.sfgame.de"], &[], &["s1.sfgame.de", "s2.sfgame.de"]),
+            (&[], &["s1.*"], &["s2.sfgame.de", "w1.sfgame.net"]),
+            (
+                &["*.sfgame.de", "w*"],
+                &["s2.*"],
+                &["s1.sfgame.de", "w1.sfgame.net"],
+            ),
+            // A server, that is included and excluded, is not crawled
+            (&["s1.sfgame.de"], &["*.de"], &[]),
+        ];
+        for (include, exclude, expected) in cases {
+            let res = filter_servers(
+                servers.clone(),
+                &patterns(include),
+                &patterns(exclude),
+            );
+            assert_eq!(res, urls(expected), "{include:?} {exclude:?}");
+        }
+    }
+
+    #[test]
+    fn default_exclude() {
+        let exclude: Vec<ServerPattern> = Config::default()
+            .crawl_exclude
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let servers = urls(&["https://speed.sfgame.net/", "s1.sfgame.net"]);
+        assert_eq!(
+            filter_servers(servers, &[], &exclude),
+            urls(&["s1.sfgame.net"])
+        );
+    }
+
+    #[test]
+    fn explicit_urls_ignore_default_exclude() {
+        let select = ServerSelect {
+            source: ServerSource {
+                all: false,
+                urls: Some(urls(&["speed.sfgame.net", "s1.sfgame.net"])),
+                file: None,
+            },
+            include: vec![],
+            exclude: patterns(&["s1.*"]),
+            no_default_exclude: false,
+        };
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let res = rt.block_on(select.resolve(Config::default().crawl_exclude));
+        assert_eq!(res, Ok(urls(&["speed.sfgame.net"])));
+    }
+}
@@ -11,6 +11,7 @@ use sf_api::{
 };
 
 use crate::{
+    config::NakedRules,
     crawler::{CrawlAction, CrawlerState, WorkerQue},
     player::AccountInfo,
     AccountID, AccountIdent, CharacterInfo, QueID, ServerID,
@@ -44,6 +45,8 @@ pub struct ServerInfo {
     pub crawling: CrawlingStatus,
     pub connection: ServerConnection,
     pub headless_progress: Option<indicatif::ProgressBar>,
+    /// Changes to the naked rules, that have not been applied yet
+    pub naked_draft: Option<NakedRules>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -91,6 +94,7 @@ impl Servers {
                 crawling: CrawlingStatus::Waiting,
                 connection,
                 headless_progress: pb,
+                naked_draft: None,
             });
         server
     }
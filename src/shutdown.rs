@@ -0,0 +1,117 @@
+use std::{pin::pin, time::Duration};
+
+use iced::{futures::future::select, Command};
+use log::{info, warn};
+use tokio::time::sleep;
+
+use crate::{message::Message, server::CrawlingStatus, Helper};
+
+/// How often we check, if the in-flight crawl actions are done
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+/// How long we wait for in-flight crawl actions, before we save anyways
+const MAX_WAIT_CHECKS: u32 = 20;
+
+/// Waits until the process is asked to stop via Ctrl-C, or SIGTERM
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            let ctrl_c = pin!(tokio::signal::ctrl_c());
+            let term = pin!(term.recv());
+            select(ctrl_c, term).await;
+            return;
+        }
+    }
+    _ = tokio::signal::ctrl_c().await;
+}
+
+impl Helper {
+    /// Stops all crawlers from starting new actions and writes the progress
+    /// of every server, before the process exits
+    pub fn request_shutdown(&mut self) -> Command<Message> {
+        if self.shutting_down {
+            warn!("Stopping immediately");
+            std::process::exit(130);
+        }
+        info!("Shutting down. Saving the crawling progress");
+        self.shutting_down = true;
+        for server in self.servers.0.values() {
+            if let CrawlingStatus::Crawling { que, .. } = &server.crawling {
+                que.lock().unwrap().paused = true;
+            }
+        }
+        Command::perform(sleep(WAIT_INTERVAL), |_| Message::ShutdownWait(1))
+    }
+
+    pub fn shutdown_wait(&mut self, checks: u32) -> Command<Message> {
+        let in_flight = self.servers.0.values().any(|a| match &a.crawling {
+            CrawlingStatus::Crawling { que, .. } => {
+                let que = que.lock().unwrap();
+                !que.in_flight_pages.is_empty()
+                    || !que.in_flight_accounts.is_empty()
+            }
+            _ => false,
+        });
+        if in_flight && checks < MAX_WAIT_CHECKS {
+            return Command::perform(sleep(WAIT_INTERVAL), move |_| {
+                Message::ShutdownWait(checks + 1)
+            });
+        }
+
+        // The servers of the CLI are written to their own output directory
+        // and end the run, once they are done
+        let mut commands = vec![];
+        if let Some(cli) = &mut self.cli_crawling {
+            cli.interrupted = true;
+            commands.push(self.stop_cli_run());
+        }
+
+        for server in self.servers.0.values() {
+            let CrawlingStatus::Crawling {
+                que, player_info, ..
+            } = &server.crawling
+            else {
+                continue;
+            };
+            if player_info.is_empty() {
+                continue;
+            }
+            let backup = que.lock().unwrap().create_backup(player_info);
+            let ident = server.ident.ident.clone();
+            self.pending_backups += 1;
+            commands.push(Command::perform(
+                async move { backup.write(&ident).await },
+                |res| Message::ShutdownBackupWritten {
+                    error: res.err().map(|a| a.to_string()),
+                },
+            ));
+        }
+        if self.pending_backups == 0 && self.cli_crawling.is_none() {
+            return self.exit();
+        }
+        Command::batch(commands)
+    }
+
+    pub fn shutdown_backup_written(
+        &mut self,
+        error: Option<String>,
+    ) -> Command<Message> {
+        if let Some(error) = error {
+            warn!("Could not write backup: {error}");
+        }
+        self.pending_backups = self.pending_backups.saturating_sub(1);
+        if self.pending_backups == 0 && self.cli_crawling.is_none() {
+            return self.exit();
+        }
+        Command::none()
+    }
+
+    fn exit(&self) -> Command<Message> {
+        info!("Finished shutting down");
+        match self.is_headless() {
+            true => std::process::exit(0),
+            false => iced::window::close(iced::window::Id::MAIN),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sf_api::{
+    gamestate::{unlockables::ScrapBook, GameState},
+    session::{PWHash, ServerConnection, Session},
+};
+
+use crate::{
+    backup::ZHofBackup, battle_order, calc_per_player_count, config::Config,
+    find_best, handle_new_char_info, player::ScrapbookInfo,
+    server::ServerIdent, sort_targets, AttackTarget,
+};
+
+#[derive(Debug, clap::Args, Clone)]
+pub struct TargetArgs {
+    /// The url of the server, the targets are on
+    #[arg(short, long)]
+    server: String,
+    /// The HoF backup to read the players from. Defaults to the backup of
+    /// the server in the current directory
+    #[arg(long)]
+    hof: Option<PathBuf>,
+    #[clap(flatten)]
+    source: ScrapbookSource,
+    /// A file containing the password of the character given via `--name`.
+    /// Without this, the password is read from the `SF_HELPER_PASSWORD`
+    /// environment variable, or from stdin
+    #[arg(long, requires = "name")]
+    password_file: Option<PathBuf>,
+    /// Stores the scrapbook of the logged in character in this file, so that
+    /// it can be used via `--scrapbook` later
+    #[arg(long, requires = "name")]
+    save_scrapbook: Option<PathBuf>,
+    /// Only show players up to this level. Defaults to the level of the
+    /// character
+    #[arg(long)]
+    max_level: Option<u16>,
+    /// Only show players up to these total attributes. Defaults to what the
+    /// character can expect to beat
+    #[arg(long)]
+    max_attributes: Option<u32>,
+    /// The maximum amount of targets to show
+    #[arg(short, long, default_value_t = 50)]
+    limit: usize,
+    /// Shows the order in which to fight the targets, so that every fight
+    /// gives new items, instead of the best targets right now
+    #[arg(long)]
+    order: bool,
+    /// Prints the targets as JSON instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, clap::Args, Clone)]
+#[group(required = true, multiple = false)]
+pub struct ScrapbookSource {
+    /// The name of the character to log in with and read the scrapbook of
+    #[arg(short, long)]
+    name: Option<String>,
+    /// A scrapbook previously stored via `--save-scrapbook`
+    #[arg(long)]
+    scrapbook: Option<PathBuf>,
+}
+
+/// Everything we need to know about a character to find its targets without
+/// logging in
+#[derive(Debug, Serialize, Deserialize)]
+struct ScrapbookSnapshot {
+    name: String,
+    server: String,
+    max_level: u16,
+    max_attributes: u32,
+    scrapbook: ScrapBook,
+}
+
+#[derive(Debug, Serialize)]
+struct TargetOutput<'a> {
+    name: &'a str,
+    uid: u32,
+    level: u16,
+    items: usize,
+    missing: usize,
+    /// The weighted amount of new items, that the targets are ranked by
+    score: usize,
+}
+
+/// The environment variable, that can contain the password of the character
+const PASSWORD_ENV: &str = "SF_HELPER_PASSWORD";
+
+/// Reads the password from the file, the environment, or stdin, so that it
+/// never shows up in the process list or the shell history
+fn read_password(file: Option<&Path>) -> std::io::Result<String> {
+    if let Some(path) = file {
+        let password = std::fs::read_to_string(path)?;
+        return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    eprint!("Password: ");
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Prints the best targets for a character and returns the exit code
+pub fn print_targets(args: TargetArgs, config: &Path) -> i32 {
+    let rt = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("Could not start the runtime: {e}");
+            return 1;
+        }
+    };
+    match rt.block_on(find_targets(args, config)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+async fn find_targets(
+    args: TargetArgs,
+    config: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::restore(config)?;
+    let server = ServerIdent::new(&args.server);
+
+    let snapshot = match (&args.source.name, &args.source.scrapbook) {
+        (Some(name), _) => {
+            let password = read_password(args.password_file.as_deref())?;
+            if password.is_empty() {
+                return Err("A password is required to log in".into());
+            }
+            let snapshot =
+                login_snapshot(&args.server, name, &password).await?;
+            if let Some(path) = &args.save_scrapbook {
+                std::fs::write(path, serde_json::to_string(&snapshot)?)?;
+            }
+            snapshot
+        }
+        (None, Some(path)) => {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        }
+        (None, None) => {
+            return Err("Either a character, or a scrapbook is required".into())
+        }
+    };
+    if ServerIdent::new(&snapshot.server).id != server.id {
+        return Err(format!(
+            "The scrapbook of {} is from {}, not {}",
+            snapshot.name, snapshot.server, args.server
+        )
+        .into());
+    }
+
+    let backup = match &args.hof {
+        Some(path) => ZHofBackup::read_path(path).await,
+        None => ZHofBackup::read(&server.ident).await,
+    }
+    .map_err(|e| format!("Could not read the HoF backup: {e}"))?;
+
+    let mut equipment = Default::default();
+    let mut player_info = Default::default();
+    let mut naked = Default::default();
+    let naked_rules = config.naked_rules(&server.ident);
+    for char in backup.characters {
+        handle_new_char_info(
+            char, &mut equipment, &mut player_info, &mut naked, naked_rules,
+        );
+    }
+    let invalid: HashSet<&str> =
+        backup.invalid_accounts.iter().map(|a| a.as_str()).collect();
+
+    let mut si = ScrapbookInfo::from_scrapbook(
+        snapshot.scrapbook,
+        args.max_level.unwrap_or(snapshot.max_level),
+        args.max_attributes.unwrap_or(snapshot.max_attributes),
+    );
+    if let Some(char_conf) = config.get_char_conf(&snapshot.name, server.id) {
+        si.blacklist = char_conf
+            .blacklist
+            .iter()
+            .map(|b| (b.uid, b.clone()))
+            .collect();
+    }
+
+    let epic_weight = config.epic_weight();
+    let mut targets = if args.order {
+        battle_order(
+            &player_info, &equipment, &si.scrapbook.items, &si, &invalid,
+            config.blacklist_threshold, epic_weight,
+        )
+    } else {
+        let per_player_counts = calc_per_player_count(
+            &player_info, &equipment, &si.scrapbook.items, &si,
+            config.blacklist_threshold, epic_weight,
+        );
+        let mut best =
+            find_best(&per_player_counts, &player_info, args.limit, &invalid);
+        sort_targets(&mut best);
+        best
+    };
+    targets.truncate(args.limit);
+
+    match args.json {
+        true => print_json(&targets)?,
+        false => print_table(&targets),
+    }
+    Ok(())
+}
+
+async fn login_snapshot(
+    server: &str,
+    name: &str,
+    password: &str,
+) -> Result<ScrapbookSnapshot, Box<dyn std::error::Error>> {
+    let con = ServerConnection::new(server).ok_or("Invalid server url")?;
+    let mut session = Session::new_hashed(name, PWHash::new(password), con);
+    let resp = session.login().await?;
+    let gs = GameState::new(resp)?;
+    let si = ScrapbookInfo::new(&gs, None)
+        .ok_or("The scrapbook of this character is not unlocked")?;
+    Ok(ScrapbookSnapshot {
+        name: name.to_string(),
+        server: server.to_string(),
+        max_level: si.max_level,
+        max_attributes: si.max_attributes,
+        scrapbook: si.scrapbook,
+    })
+}
+
+fn print_json(targets: &[AttackTarget]) -> Result<(), serde_json::Error> {
+    let out: Vec<_> = targets
+        .iter()
+        .map(|a| TargetOutput {
+            name: &a.info.name,
+            uid: a.info.uid,
+            level: a.info.level,
+            items: a.info.equipment.len(),
+            missing: a.missing,
+            score: a.score,
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&out)?);
+    Ok(())
+}
+
+fn print_table(targets: &[AttackTarget]) {
+    println!(
+        "{:>4}  {:>5}  {:>7}  {:>5}  {:>5}  name",
+        "#", "score", "missing", "level", "items"
+    );
+    for (pos, target) in targets.iter().enumerate() {
+        println!(
+            "{:>4}  {:>5}  {:>7}  {:>5}  {:>5}  {}",
+            pos + 1,
+            target.score,
+            target.missing,
+            target.info.level,
+            target.info.equipment.len(),
+            target.info.name
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use iced::{
+    alignment::Horizontal,
+    widget::{column, row, scrollable, text, Column},
+    Alignment, Element, Length,
+};
+use num_format::ToFormattedString;
+use sf_api::gamestate::{character::Class, items::EquipmentSlot};
+
+use crate::{
+    completion::{CompletionCount, MODEL_RANGE_SIZE},
+    config::Config,
+    message::Message,
+    player::AccountInfo,
+};
+
+pub fn view_completion<'a>(
+    player: &'a AccountInfo,
+    config: &'a Config,
+) -> Element<'a, Message> {
+    let Some(si) = &player.scrapbook_info else {
+        return text("Player does not have a scrapbook").size(20).into();
+    };
+    let completion = &si.completion;
+
+    let mut all = column!()
+        .spacing(20)
+        .padding(15)
+        .width(Length::Fixed(500.0))
+        .align_items(Alignment::Center);
+
+    all = all.push(completion_table(
+        "Total",
+        [("All Items".to_string(), &completion.total)],
+        config,
+    ));
+
+    let mut slots: Vec<_> = completion.by_slot.iter().collect();
+    slots.sort_by_key(|a| a.0.raw_id());
+    all = all.push(completion_table(
+        "Item Type",
+        slots
+            .into_iter()
+            .map(|(slot, count)| (slot_name(*slot), count)),
+        config,
+    ));
+
+    let mut classes: Vec<_> = completion.by_class.iter().collect();
+    classes.sort_by_key(|a| a.0.map(|a| a as u8 + 1).unwrap_or_default());
+    all = all.push(completion_table(
+        "Class",
+        classes
+            .into_iter()
+            .map(|(class, count)| (class_name(*class), count)),
+        config,
+    ));
+
+    all = all.push(completion_table(
+        "Model ID",
+        completion.by_model.iter().map(|(start, count)| {
+            (format!("{start} - {}", start + MODEL_RANGE_SIZE - 1), count)
+        }),
+        config,
+    ));
+
+    column!(scrollable(all))
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .align_items(Alignment::Center)
+        .into()
+}
+
+fn completion_table<'a>(
+    title: &'a str,
+    rows: impl IntoIterator<Item = (String, &'a CompletionCount)>,
+    config: &'a Config,
+) -> Element<'a, Message> {
+    let cell = |t: String| {
+        text(t)
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Center)
+    };
+
+    let mut table: Column<Message> = column!().spacing(5);
+    table = table.push(row!(
+        text(title)
+            .size(16)
+            .width(Length::FillPortion(2))
+            .horizontal_alignment(Horizontal::Left),
+        cell("Found".to_string()),
+        cell("Obtainable".to_string()),
+        cell("Unheld".to_string()),
+    ));
+
+    for (name, count) in rows {
+        table = table.push(row!(
+            text(name)
+                .width(Length::FillPortion(2))
+                .horizontal_alignment(Horizontal::Left),
+            cell(count.found.to_formatted_string(&config.num_format)),
+            cell(count.obtainable.to_formatted_string(&config.num_format)),
+            cell(count.unheld.to_formatted_string(&config.num_format)),
+        ));
+    }
+    table.into()
+}
+
+pub fn slot_name(slot: EquipmentSlot) -> String {
+    match slot {
+        EquipmentSlot::BreastPlate => "Breast Plate".to_string(),
+        EquipmentSlot::FootWear => "Foot Wear".to_string(),
+        x => format!("{x:?}"),
+    }
+}
+
+fn class_name(class: Option<Class>) -> String {
+    match class {
+        None => "Any".to_string(),
+        Some(Class::BattleMage) => "Battle Mage".to_string(),
+        Some(Class::DemonHunter) => "Demon Hunter".to_string(),
+        Some(x) => format!("{x:?}"),
+    }
+}
@@ -0,0 +1,208 @@
+use iced::{
+    alignment::Horizontal,
+    theme,
+    widget::{
+        button, column, horizontal_space, pick_list, row, scrollable, text,
+        text_input, Column,
+    },
+    Alignment, Element, Length,
+};
+use iced_aw::number_input;
+
+use crate::{
+    history::{HistoryFilter, HistoryKind, KindFilter, ResultFilter},
+    message::Message,
+    player::AccountInfo,
+};
+
+/// The maximum amount of single entries, that we render at once
+const MAX_SHOWN_ENTRIES: usize = 500;
+
+pub fn view_history<'a>(
+    player: &'a AccountInfo,
+    filter: &'a HistoryFilter,
+    export_status: Option<&'a str>,
+) -> Element<'a, Message> {
+    let history = &player.history;
+
+    let mut left_col = column!().align_items(Alignment::Center).spacing(10);
+
+    let kind = pick_list(
+        [KindFilter::All, KindFilter::Fights, KindFilter::Lures],
+        Some(filter.kind),
+        |nv| {
+            Message::SetHistoryFilter(HistoryFilter {
+                kind: nv,
+                ..filter.clone()
+            })
+        },
+    );
+    left_col = left_col.push(
+        row!(text("Kind:"), horizontal_space(), kind)
+            .align_items(Alignment::Center),
+    );
+
+    let result = pick_list(
+        [ResultFilter::All, ResultFilter::Won, ResultFilter::Lost],
+        Some(filter.result),
+        |nv| {
+            Message::SetHistoryFilter(HistoryFilter {
+                result: nv,
+                ..filter.clone()
+            })
+        },
+    );
+    left_col = left_col.push(
+        row!(text("Result:"), horizontal_space(), result)
+            .align_items(Alignment::Center),
+    );
+
+    let opponent = text_input("Opponent", &filter.opponent).on_input(|nv| {
+        Message::SetHistoryFilter(HistoryFilter {
+            opponent: nv,
+            ..filter.clone()
+        })
+    });
+    left_col = left_col.push(opponent);
+
+    let days = number_input(filter.days, 3650, Message::SetHistoryDays)
+        .style(iced_aw::NumberInputStyles::Default);
+    left_col = left_col.push(
+        row!(text("Last days:"), horizontal_space(), days)
+            .align_items(Alignment::Center),
+    );
+
+    left_col =
+        left_col.push(button("Export CSV").on_press(Message::ExportHistory {
+            ident: player.ident,
+        }));
+    if let Some(status) = export_status {
+        left_col = left_col.push(text(status).size(12));
+    }
+
+    let lures = history.lure_stats(filter);
+    if lures.total() > 0 {
+        let stat = |name, val: String| {
+            row!(
+                text(name).width(Length::FillPortion(1)),
+                text(val)
+                    .width(Length::FillPortion(1))
+                    .horizontal_alignment(Horizontal::Right),
+            )
+        };
+        left_col = left_col.push(text("Lure Results").size(16));
+        left_col = left_col
+            .push(stat("Won:", format!("{}/{}", lures.won, lures.total())));
+        left_col = left_col.push(stat(
+            "Success:",
+            format!("{:.0}%", lures.win_rate() * 100.0),
+        ));
+        left_col = left_col.push(stat("Silver:", lures.silver.to_string()));
+        left_col = left_col.push(stat("Souls:", lures.souls.to_string()));
+        left_col = left_col.push(stat(
+            "Avg Souls:",
+            (lures.souls / lures.total() as u64).to_string(),
+        ));
+    }
+
+    let cell = |t: String, portion: u16| {
+        text(t)
+            .width(Length::FillPortion(portion))
+            .horizontal_alignment(Horizontal::Center)
+    };
+
+    let mut daily: Column<Message> = column!().spacing(5);
+    daily = daily.push(row!(
+        text("Day")
+            .size(16)
+            .width(Length::FillPortion(3))
+            .horizontal_alignment(Horizontal::Left),
+        cell("Fights".to_string(), 2),
+        cell("Lures".to_string(), 2),
+        cell("Items".to_string(), 2),
+        cell("Mushrooms".to_string(), 2),
+    ));
+    for (day, summary) in history.daily(filter).iter().rev() {
+        daily = daily.push(row!(
+            text(day.format("%Y-%m-%d"))
+                .width(Length::FillPortion(3))
+                .horizontal_alignment(Horizontal::Left),
+            cell(format!("{}/{}", summary.fights_won, summary.fights_lost), 2),
+            cell(format!("{}/{}", summary.lures_won, summary.lures_lost), 2),
+            cell(summary.items.to_string(), 2),
+            cell(summary.mushrooms.to_string(), 2),
+        ));
+    }
+
+    let mut entries: Column<Message> = column!().spacing(5);
+    entries = entries.push(row!(
+        text("Time")
+            .width(Length::FillPortion(4))
+            .horizontal_alignment(Horizontal::Left),
+        cell("Kind".to_string(), 2),
+        text("Opponent")
+            .width(Length::FillPortion(6))
+            .horizontal_alignment(Horizontal::Left),
+        cell("Level".to_string(), 2),
+        cell("Result".to_string(), 2),
+        cell("Items".to_string(), 2),
+        cell("Mushrooms".to_string(), 2),
+        cell("Silver".to_string(), 2),
+        cell("Souls".to_string(), 2),
+    ));
+    for entry in history.filtered(filter).rev().take(MAX_SHOWN_ENTRIES) {
+        let kind = match entry.kind {
+            HistoryKind::Fight => "Fight",
+            HistoryKind::Lure => "Lure",
+        };
+        let result = button(text(if entry.won { "Won" } else { "Lost" }))
+            .padding(2)
+            .style(match entry.won {
+                true => theme::Button::Positive,
+                false => theme::Button::Destructive,
+            });
+
+        entries = entries.push(
+            row!(
+                text(entry.time.format("%Y-%m-%d %H:%M"))
+                    .width(Length::FillPortion(4))
+                    .horizontal_alignment(Horizontal::Left),
+                cell(kind.to_string(), 2),
+                text(&entry.opponent)
+                    .width(Length::FillPortion(6))
+                    .horizontal_alignment(Horizontal::Left),
+                cell(
+                    match entry.opponent_level {
+                        0 => String::new(),
+                        lvl => format!("{lvl} ({})", entry.opponent_items),
+                    },
+                    2
+                ),
+                column!(result)
+                    .align_items(Alignment::Center)
+                    .width(Length::FillPortion(2)),
+                cell(entry.items.to_string(), 2),
+                cell(entry.mushrooms.to_string(), 2),
+                cell(entry.silver.to_string(), 2),
+                cell(entry.souls.to_string(), 2),
+            )
+            .align_items(Alignment::Center),
+        );
+    }
+
+    let right_col = column!(
+        scrollable(daily).height(Length::Fixed(200.0)),
+        scrollable(entries).height(Length::Fill)
+    )
+    .spacing(20);
+
+    row!(
+        left_col.width(Length::Fixed(200.0)),
+        right_col.width(Length::Fill)
+    )
+    .padding(15)
+    .spacing(15)
+    .height(Length::Fill)
+    .align_items(Alignment::Start)
+    .into()
+}
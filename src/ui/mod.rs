@@ -6,7 +6,8 @@ use iced::{
     theme,
     widget::{
         self, button, checkbox, column, container, horizontal_space, pick_list,
-        progress_bar, row, text, Button,
+        progress_bar, row, scrollable, text, text_input, tooltip,
+        vertical_space, Button,
     },
     Alignment, Element, Length,
 };
@@ -14,23 +15,30 @@ use iced_aw::{number_input, widgets::DropDown};
 use num_format::ToFormattedString;
 use options::view_options;
 
-use self::{scrapbook::view_scrapbook, underworld::view_underworld};
+use self::{
+    completion::view_completion, history::view_history,
+    scrapbook::view_scrapbook, underworld::view_underworld,
+};
 use crate::{
-    config::{AvailableTheme, Config},
+    config::{AvailableTheme, Config, EpicPolicy},
     crawler::CrawlingOrder,
     get_server_code,
     message::Message,
+    notify::{NotifyKind, WebhookFormat},
     player::{AccountInfo, AccountStatus},
+    schedule::ScheduleKind,
     server::{CrawlingStatus, ServerInfo},
     top_bar, AccountIdent, AccountPage, ActionSelection, Helper, View,
 };
 
+mod completion;
+mod history;
 mod options;
 mod scrapbook;
 pub mod underworld;
 
 impl Helper {
-    pub fn view_current_page(&self) -> Element<Message> {
+    pub fn view_current_page(&self) -> Element<'_, Message> {
         let view: Element<Message> = match &self.current_view {
             View::Account { ident, page } => self.view_account(*ident, *page),
             View::Login => self
@@ -40,6 +48,9 @@ impl Helper {
                 self.view_overview(selected, action)
             }
             View::Settings => self.view_settings(),
+            View::Unlock => self.view_unlock(),
+            View::InvalidConfig { error } => self.view_invalid_config(error),
+            View::SelectProfile => self.view_select_profile(),
         };
         let main_part = container(view).width(Length::Fill).center_x();
         let mut res = column!();
@@ -74,7 +85,7 @@ impl Helper {
         &self,
         ident: AccountIdent,
         page: AccountPage,
-    ) -> Element<Message> {
+    ) -> Element<'_, Message> {
         let Some((server, player)) = self.servers.get_ident(&ident) else {
             return self
                 .login_state
@@ -102,7 +113,9 @@ impl Helper {
                 .horizontal_alignment(iced::alignment::Horizontal::Right)
                 .size(20),
             selection(AccountPage::Scrapbook),
+            selection(AccountPage::Completion),
             selection(AccountPage::Underworld),
+            selection(AccountPage::History),
             selection(AccountPage::Options),
             button(text("Logout"))
                 .on_press(Message::RemoveAccount {
@@ -120,9 +133,15 @@ impl Helper {
             AccountPage::Scrapbook => {
                 view_scrapbook(server, player, &self.config, &self.class_images)
             }
+            AccountPage::Completion => view_completion(player, &self.config),
             AccountPage::Underworld => view_underworld(
                 server, player, &self.config, &self.class_images,
             ),
+            AccountPage::History => view_history(
+                player,
+                &self.history_filter,
+                self.history_export.as_deref(),
+            ),
             AccountPage::Options => view_options(player, server, &self.config),
         };
 
@@ -135,7 +154,7 @@ impl Helper {
             .into()
     }
 
-    fn view_settings(&self) -> Element<Message> {
+    fn view_settings(&self) -> Element<'_, Message> {
         let top_row = top_bar(
             text("Settings").size(20).into(),
             if self.has_accounts() {
@@ -217,14 +236,67 @@ impl Helper {
         .width(Length::Fill)
         .align_items(Alignment::Center);
 
-        let settings_column = column!(
+        let blacklist_expiry = number_input(
+            self.config.blacklist_expiry_days,
+            365,
+            Message::SetBlacklistExpiry,
+        );
+
+        let blacklist_expiry = row!(
+            "Blacklist expiry (days):",
+            horizontal_space(),
+            blacklist_expiry
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let epic_policy = pick_list(
+            [EpicPolicy::Ignore, EpicPolicy::Count, EpicPolicy::Weighted],
+            Some(self.config.epic_policy),
+            Message::SetEpicPolicy,
+        );
+
+        let epic_policy = row!("Epic items:", horizontal_space(), epic_policy)
+            .width(Length::Fill)
+            .align_items(Alignment::Center);
+
+        let mut settings_column = column!(
             theme_row, auto_fetch_hof, auto_poll, max_threads, start_threads,
-            blacklist_threshold, crawling_restrict, show_class_icons
+            blacklist_threshold, blacklist_expiry, epic_policy
         )
         .width(Length::Fixed(300.0))
         .spacing(20);
 
-        column!(top_row, settings_column)
+        if self.config.epic_policy == EpicPolicy::Weighted {
+            let epic_weight = number_input(
+                self.config.epic_weight,
+                10,
+                Message::SetEpicWeight,
+            );
+            let epic_weight =
+                row!("Epic weight:", horizontal_space(), epic_weight)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center);
+            settings_column = settings_column.push(epic_weight);
+        }
+
+        settings_column = settings_column
+            .push(crawling_restrict)
+            .push(show_class_icons);
+
+        let right_column = column!(
+            self.view_profile_settings(),
+            self.view_notify_settings(),
+            self.view_api_settings(),
+            self.view_master_password_settings()
+        )
+        .spacing(40);
+
+        let columns = row!(settings_column, right_column)
+            .spacing(50)
+            .align_items(Alignment::Start);
+
+        column!(top_row, scrollable(columns))
             .spacing(20)
             .height(Length::Fill)
             .width(Length::Fill)
@@ -232,11 +304,277 @@ impl Helper {
             .into()
     }
 
+    fn view_notify_settings(&self) -> Element<'_, Message> {
+        let config = &self.config.notifications;
+        let mut col = column!(text("Notifications").size(18))
+            .width(Length::Fixed(400.0))
+            .spacing(15);
+
+        for kind in NotifyKind::ALL {
+            col = col.push(
+                checkbox(kind.title(), config.is_enabled(kind)).on_toggle(
+                    move |enabled| Message::SetNotifyEvent { kind, enabled },
+                ),
+            );
+        }
+
+        let cooldown = number_input(
+            config.cooldown_minutes,
+            24 * 60,
+            Message::SetNotifyCooldown,
+        )
+        .style(iced_aw::NumberInputStyles::Default);
+        col = col.push(
+            row!("Repeat after (min):", horizontal_space(), cooldown)
+                .align_items(Alignment::Center),
+        );
+
+        for (idx, webhook) in config.webhooks.iter().enumerate() {
+            let format = pick_list(
+                [
+                    WebhookFormat::Discord,
+                    WebhookFormat::Slack,
+                    WebhookFormat::Generic,
+                ],
+                Some(webhook.format),
+                move |format| Message::WebhookSetFormat { idx, format },
+            );
+            col = col.push(
+                row!(
+                    text(&webhook.url).width(Length::Fill),
+                    format,
+                    button("Remove")
+                        .on_press(Message::WebhookRemove(idx))
+                        .style(theme::Button::Destructive)
+                )
+                .spacing(10)
+                .align_items(Alignment::Center),
+            );
+        }
+
+        let new_webhook = text_input("Webhook URL", &self.new_webhook)
+            .on_input(Message::WebhookInput)
+            .on_submit(Message::WebhookAdd);
+        col = col.push(
+            row!(new_webhook, button("Add").on_press(Message::WebhookAdd))
+                .spacing(10)
+                .align_items(Alignment::Center),
+        );
+
+        let mut test = button("Send Test");
+        if !config.webhooks.is_empty() {
+            test = test.on_press(Message::WebhookTest);
+        }
+        col = col.push(test);
+        if let Some(status) = &self.webhook_status {
+            col = col.push(text(status));
+        }
+        col.into()
+    }
+
+    fn view_api_settings(&self) -> Element<'_, Message> {
+        let config = &self.config.api;
+        let mut col = column!(text("Local API").size(18))
+            .width(Length::Fixed(400.0))
+            .spacing(15);
+
+        col = col.push(
+            checkbox("Enable API", config.enabled)
+                .on_toggle(Message::SetApiEnabled),
+        );
+        col = col.push(
+            checkbox("Export Prometheus /metrics", config.metrics)
+                .on_toggle(Message::SetMetricsEnabled),
+        );
+
+        let bind = text_input(&config.bind.to_string(), &self.api_bind)
+            .on_input(Message::ApiBindInput)
+            .on_submit(Message::SetApiBind)
+            .width(Length::Fixed(150.0));
+        col = col.push(
+            row!("Address:", horizontal_space(), bind)
+                .align_items(Alignment::Center),
+        );
+
+        let port = number_input(config.port, u16::MAX, Message::SetApiPort)
+            .style(iced_aw::NumberInputStyles::Default);
+        col = col.push(
+            row!("Port:", horizontal_space(), port)
+                .align_items(Alignment::Center),
+        );
+        if !config.bind.is_loopback() {
+            col = col.push(text(
+                "The API can be reached from other machines. Keep the token \
+                 secret",
+            ));
+        }
+
+        let token =
+            text_input("Token", &config.token).on_input(Message::SetApiToken);
+        col = col.push(
+            row!(
+                token,
+                button("Generate").on_press(Message::GenerateApiToken)
+            )
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+        col.into()
+    }
+
+    fn view_profile_settings(&self) -> Element<'_, Message> {
+        let profiles = &self.profiles;
+        let mut col = column!(text("Profile").size(18))
+            .width(Length::Fixed(400.0))
+            .spacing(15);
+
+        let current = pick_list(
+            profiles.available.as_slice(),
+            Some(profiles.current.clone()),
+            Message::SwitchProfile,
+        );
+        col = col.push(
+            row!("Current profile:", horizontal_space(), current)
+                .align_items(Alignment::Center),
+        );
+
+        let new_profile = text_input("Profile name", &profiles.new_name)
+            .on_input(Message::ProfileNameInput)
+            .on_submit(Message::CreateProfile);
+        col = col.push(
+            row!(
+                new_profile,
+                button("Create").on_press(Message::CreateProfile)
+            )
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+        if let Some(status) = &profiles.status {
+            col = col.push(text(status));
+        }
+        col.into()
+    }
+
+    fn view_select_profile(&self) -> Element<'_, Message> {
+        let mut col = column!(text("Select a profile").size(20))
+            .padding(20)
+            .spacing(10)
+            .width(Length::Fixed(300.0))
+            .align_items(Alignment::Center);
+        for name in &self.profiles.available {
+            col = col.push(
+                button(text(name).horizontal_alignment(Horizontal::Center))
+                    .on_press(Message::SwitchProfile(name.clone()))
+                    .width(Length::Fill)
+                    .padding(4),
+            );
+        }
+        column!(vertical_space(), col, vertical_space())
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    fn view_master_password_settings(&self) -> Element<'_, Message> {
+        let mut col = column!(text("Master Password").size(18))
+            .width(Length::Fixed(400.0))
+            .spacing(15);
+
+        col = col.push(text(
+            "Encrypts the saved account & crawler passwords in the config. It \
+             has to be entered on every start",
+        ));
+
+        let label = match self.config.encryption.is_some() {
+            true => "Change",
+            false => "Set",
+        };
+        let password = text_input("Master password", &self.master_password)
+            .on_input(Message::MasterPasswordInput)
+            .on_submit(Message::SetMasterPassword)
+            .secure(true);
+        let set = button(label).on_press_maybe(
+            (!self.deriving_key).then_some(Message::SetMasterPassword),
+        );
+        let mut row = row!(password, set)
+            .spacing(10)
+            .align_items(Alignment::Center);
+        if self.config.encryption.is_some() {
+            row = row.push(
+                button("Remove")
+                    .on_press(Message::RemoveMasterPassword)
+                    .style(theme::Button::Destructive),
+            );
+        }
+        col = col.push(row);
+        if let Some(status) = &self.master_password_status {
+            col = col.push(text(status));
+        }
+        col.into()
+    }
+
+    fn view_unlock(&self) -> Element<'_, Message> {
+        let title = text("Unlock").size(20);
+        let info = text("The saved accounts are encrypted");
+        let password = text_input("Master password", &self.master_password)
+            .on_input(Message::MasterPasswordInput)
+            .on_submit(Message::UnlockConfig)
+            .secure(true);
+        let unlock = button("Unlock")
+            .on_press_maybe(
+                (!self.deriving_key).then_some(Message::UnlockConfig),
+            )
+            .padding(4);
+
+        let mut col = column!(title, info, password, unlock)
+            .padding(20)
+            .spacing(10)
+            .width(Length::Fixed(300.0))
+            .align_items(Alignment::Center);
+        if let Some(error) = &self.master_password_status {
+            col = col.push(text(format!("Error: {error}")));
+        }
+        column!(vertical_space(), col, vertical_space())
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    fn view_invalid_config<'a>(
+        &'a self,
+        error: &'a str,
+    ) -> Element<'a, Message> {
+        let title = text("Invalid Config").size(20);
+        let path = text(self.config.path.display().to_string());
+        let info = text(
+            "Fix the file and retry, or start with a new config. The current \
+             file is kept next to it with the .invalid extension",
+        );
+        let buttons = row!(
+            button("Retry").on_press(Message::ReloadConfig).padding(4),
+            button("Start with a new config")
+                .on_press(Message::ResetConfig)
+                .style(theme::Button::Destructive)
+                .padding(4)
+        )
+        .spacing(10);
+
+        let col = column!(title, path, text(error), info, buttons)
+            .padding(20)
+            .spacing(10)
+            .width(Length::Fixed(500.0))
+            .align_items(Alignment::Center);
+        column!(vertical_space(), col, vertical_space())
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
     fn view_overview(
         &self,
         selected: &HashSet<AccountIdent>,
         currrent_action: &Option<ActionSelection>,
-    ) -> Element<Message> {
+    ) -> Element<'_, Message> {
         let top_bar =
             top_bar(text("Overview").size(20).into(), Some(Message::ViewLogin));
 
@@ -253,6 +591,7 @@ impl Helper {
             horizontal_space(),
             center(text("Underworld").width(UNDERWORLD_WIDTH)),
             center(text("Arena").width(NEXT_FIGHT_WIDTH)),
+            center(text("Mushrooms").width(MUSHROOMS_WIDTH)),
             center(text("Scrapbook").width(SCRAPBOOK_COUNT_WIDTH)),
             text("Crawling").width(CRAWLING_STATUS_WIDTH),
         )
@@ -381,7 +720,7 @@ impl Helper {
             .align_items(Alignment::Center)
             .into()
     }
-    fn overview_actions(&self) -> Element<Message> {
+    fn overview_actions(&self) -> Element<'_, Message> {
         let mut all_actions = column!().spacing(4.0);
 
         fn action(button: Button<Message>) -> Button<Message> {
@@ -437,6 +776,7 @@ const ACC_NAME_WIDTH: f32 = 200.0;
 const SERVER_CODE_WIDTH: f32 = 50.0;
 const SCRAPBOOK_COUNT_WIDTH: f32 = 60.0;
 const NEXT_FIGHT_WIDTH: f32 = 60.0;
+const MUSHROOMS_WIDTH: f32 = 80.0;
 const UNDERWORLD_WIDTH: f32 = 60.0;
 const CRAWLING_STATUS_WIDTH: f32 = 80.0;
 
@@ -449,15 +789,18 @@ fn overview_row<'a>(
     let status_text = |t: &str| center(text(t).width(ACC_STATUS_WIDTH));
 
     let mut next_free_fight = None;
+    let mut mushrooms = None;
 
     let acc_status = match &*acc.status.lock().unwrap() {
         AccountStatus::LoggingIn => status_text("Logging in"),
         AccountStatus::Idle(_, gs) => {
             next_free_fight = Some(gs.arena.next_free_fight);
+            mushrooms = Some(gs.character.mushrooms);
             status_text("Active")
         }
         AccountStatus::Busy(gs, reason) => {
             next_free_fight = Some(gs.arena.next_free_fight);
+            mushrooms = Some(gs.character.mushrooms);
             status_text(reason)
         }
         AccountStatus::FatalError(_) => status_text("Error!"),
@@ -490,7 +833,11 @@ fn overview_row<'a>(
         .scrapbook_info
         .as_ref()
         .map(|a| {
-            if a.auto_battle {
+            if a.auto_battle
+                && !acc.schedule_active(ScheduleKind::Battle, &server.ident)
+            {
+                iced_aw::Bootstrap::Pause
+            } else if a.auto_battle {
                 iced_aw::Bootstrap::LightningFill
             } else {
                 iced_aw::Bootstrap::Lightning
@@ -504,12 +851,25 @@ fn overview_row<'a>(
         Some(_) => icon_to_text(iced_aw::Bootstrap::Check),
     };
 
-    let next_free_fight = row!(
-        center(next_free_fight.width(25.0)),
-        center(icon_to_text(abs))
-    )
-    .align_items(Alignment::Center)
-    .spacing(4.0);
+    let stop_reason = acc
+        .scrapbook_info
+        .as_ref()
+        .filter(|a| !a.auto_battle)
+        .and_then(|a| a.stop_reason.as_deref());
+    let abs: Element<Message> = match stop_reason {
+        Some(reason) => tooltip(
+            center(icon_to_text(iced_aw::Bootstrap::SignStopFill)),
+            reason,
+            tooltip::Position::Bottom,
+        )
+        .style(theme::Container::Box)
+        .into(),
+        None => center(icon_to_text(abs)).into(),
+    };
+
+    let next_free_fight = row!(center(next_free_fight.width(25.0)), abs)
+        .align_items(Alignment::Center)
+        .spacing(4.0);
 
     let next_free_fight = column!(next_free_fight)
         .align_items(Alignment::Center)
@@ -519,7 +879,11 @@ fn overview_row<'a>(
         .underworld_info
         .as_ref()
         .map(|a| {
-            let auto_status = if a.auto_lure {
+            let auto_status = if a.auto_lure
+                && !acc.schedule_active(ScheduleKind::Lure, &server.ident)
+            {
+                iced_aw::Bootstrap::Pause
+            } else if a.auto_lure {
                 iced_aw::Bootstrap::LightningFill
             } else {
                 iced_aw::Bootstrap::Lightning
@@ -550,6 +914,22 @@ fn overview_row<'a>(
                 .into(),
         );
 
+    // The current amount of mushrooms & the amount spent today
+    let mushrooms = match mushrooms {
+        Some(m) => {
+            let spent = acc.history.mushrooms_spent_today();
+            if spent > 0 {
+                format!("{m} (-{spent})")
+            } else {
+                m.to_string()
+            }
+        }
+        None => String::new(),
+    };
+    let mushrooms = text(mushrooms)
+        .width(MUSHROOMS_WIDTH)
+        .horizontal_alignment(Horizontal::Center);
+
     let crawling_status = text(crawling_status).width(CRAWLING_STATUS_WIDTH);
 
     let info_row = row!(
@@ -559,6 +939,7 @@ fn overview_row<'a>(
         horizontal_space(),
         underworld_info,
         next_free_fight,
+        mushrooms,
         scrapbook_count,
         crawling_status
     )
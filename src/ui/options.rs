@@ -1,10 +1,15 @@
 use iced::{
-    widget::{checkbox, column, text},
+    widget::{checkbox, column, horizontal_space, row, text, Column},
     Alignment, Element, Length,
 };
+use iced_aw::number_input;
 
 use crate::{
-    config::Config, message::Message, player::AccountInfo, server::ServerInfo,
+    config::{BattleLimits, Config},
+    message::Message,
+    player::AccountInfo,
+    schedule::{Schedule, ScheduleKind},
+    server::ServerInfo,
 };
 
 pub fn view_options<'a>(
@@ -14,16 +19,32 @@ pub fn view_options<'a>(
 ) -> Element<'a, Message> {
     let config = config.get_char_conf(&player.name, og_server.ident.id);
 
+    let mut all = column!().spacing(20).width(Length::Fixed(300.0));
+
     let Some(config) = config else {
-        return text(
-            "Use 'Remember me' during login to store player configurations",
-        )
-        .size(20)
-        .into();
+        all = all.push(
+            text(
+                "Use 'Remember me' during login to store player configurations",
+            )
+            .size(20),
+        );
+        if let Some(si) = &player.scrapbook_info {
+            all = all.push(view_battle_limits(player, si.limits));
+        }
+        for kind in [ScheduleKind::Battle, ScheduleKind::Lure] {
+            if let Some(schedule) = player.schedule(kind) {
+                all =
+                    all.push(view_schedule(player, og_server, kind, *schedule));
+            }
+        }
+        return column!(all)
+            .padding(20)
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into();
     };
 
-    let mut all = column!().spacing(20).width(Length::Fixed(300.0));
-
     all = all.push(
         checkbox("Automatically login on startup", config.login).on_toggle(
             |nv| Message::ConfigSetAutoLogin {
@@ -54,6 +75,15 @@ pub fn view_options<'a>(
         ),
     );
 
+    if let Some(si) = &player.scrapbook_info {
+        all = all.push(view_battle_limits(player, si.limits));
+    }
+    for kind in [ScheduleKind::Battle, ScheduleKind::Lure] {
+        if let Some(schedule) = player.schedule(kind) {
+            all = all.push(view_schedule(player, og_server, kind, *schedule));
+        }
+    }
+
     column!(all)
         .padding(20)
         .height(Length::Fill)
@@ -61,3 +91,166 @@ pub fn view_options<'a>(
         .align_items(Alignment::Center)
         .into()
 }
+
+fn view_battle_limits(
+    player: &AccountInfo,
+    limits: BattleLimits,
+) -> Element<'_, Message> {
+    let ident = player.ident;
+    let limit_input = |val: u32, max: u32, f: fn(&mut BattleLimits, u32)| {
+        number_input(val, max, move |nv| {
+            let mut limits = limits;
+            f(&mut limits, nv);
+            Message::SetBattleLimits { ident, limits }
+        })
+        .style(iced_aw::NumberInputStyles::Default)
+    };
+
+    let mut col: Column<Message> = column!().spacing(10);
+    col = col.push(text("Auto-battle limits (0 = off)"));
+
+    let rows = [
+        (
+            "Min. new items:",
+            limit_input(limits.min_missing as u32, 99, |l, nv| {
+                l.min_missing = nv as usize
+            }),
+        ),
+        (
+            "Max. level above us:",
+            limit_input(limits.max_level_diff.into(), 999, |l, nv| {
+                l.max_level_diff = nv as u16
+            }),
+        ),
+        (
+            "Max. losses against:",
+            limit_input(limits.max_losses as u32, 99, |l, nv| {
+                l.max_losses = nv as usize
+            }),
+        ),
+        (
+            "Stop after losses in a row:",
+            limit_input(limits.max_consecutive_losses, 99, |l, nv| {
+                l.max_consecutive_losses = nv
+            }),
+        ),
+        (
+            "Stop at scrapbook items:",
+            limit_input(limits.target_items as u32, 99_999, |l, nv| {
+                l.target_items = nv as usize
+            }),
+        ),
+    ];
+    for (name, input) in rows {
+        col = col.push(
+            row!(text(name), horizontal_space(), input)
+                .align_items(Alignment::Center),
+        );
+    }
+    col.into()
+}
+
+fn view_schedule<'a>(
+    player: &'a AccountInfo,
+    server: &ServerInfo,
+    kind: ScheduleKind,
+    schedule: Schedule,
+) -> Element<'a, Message> {
+    let ident = player.ident;
+    let set = move |schedule| Message::SetSchedule {
+        ident,
+        kind,
+        schedule,
+    };
+
+    let name = match kind {
+        ScheduleKind::Battle => "auto-battle",
+        ScheduleKind::Lure => "auto-lure",
+    };
+
+    let mut col: Column<Message> = column!().spacing(10);
+    col = col.push(
+        checkbox(format!("Only run {name} on a schedule"), schedule.enabled)
+            .on_toggle(move |nv| {
+                set(Schedule {
+                    enabled: nv,
+                    ..schedule
+                })
+            }),
+    );
+    if !schedule.enabled {
+        return col.into();
+    }
+
+    let status = if player.schedule_active(kind, &server.ident) {
+        "Currently active"
+    } else {
+        "Currently paused"
+    };
+    col = col.push(text(status));
+
+    let start = number_input(schedule.start_hour, 23, move |nv| {
+        set(Schedule {
+            start_hour: nv,
+            ..schedule
+        })
+    })
+    .style(iced_aw::NumberInputStyles::Default);
+    col = col.push(
+        row!(text("Start hour:"), horizontal_space(), start)
+            .align_items(Alignment::Center),
+    );
+
+    let end = number_input(schedule.end_hour, 23, move |nv| {
+        set(Schedule {
+            end_hour: nv,
+            ..schedule
+        })
+    })
+    .style(iced_aw::NumberInputStyles::Default);
+    col = col.push(
+        row!(text("End hour:"), horizontal_space(), end)
+            .align_items(Alignment::Center),
+    );
+
+    let mut days = row!().spacing(5);
+    for (idx, day) in ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+        .into_iter()
+        .enumerate()
+    {
+        days = days.push(
+            checkbox(day, schedule.days[idx])
+                .on_toggle(move |nv| {
+                    let mut schedule = schedule;
+                    schedule.days[idx] = nv;
+                    set(schedule)
+                })
+                .size(14)
+                .spacing(2),
+        );
+    }
+    col = col.push(days);
+
+    let jitter = number_input(schedule.jitter_minutes, 120, move |nv| {
+        set(Schedule {
+            jitter_minutes: nv,
+            ..schedule
+        })
+    })
+    .style(iced_aw::NumberInputStyles::Default);
+    col = col.push(
+        row!(text("Random offset (min):"), horizontal_space(), jitter)
+            .align_items(Alignment::Center),
+    );
+
+    col =
+        col.push(checkbox("Use server time", schedule.server_time).on_toggle(
+            move |nv| {
+                set(Schedule {
+                    server_time: nv,
+                    ..schedule
+                })
+            },
+        ));
+    col.into()
+}
@@ -15,7 +15,7 @@ use num_format::ToFormattedString;
 
 use super::{remaining_minutes, view_crawling};
 use crate::{
-    config::Config,
+    config::{Config, MushroomPolicy},
     message::Message,
     player::{AccountInfo, AccountStatus},
     server::ServerInfo,
@@ -137,6 +137,47 @@ pub fn view_scrapbook<'a>(
             .size(20),
     );
 
+    if let Some(reason) = si.stop_reason.as_ref().filter(|_| !si.auto_battle) {
+        left_col = left_col.push(text(format!("Stopped: {reason}")));
+    }
+
+    let policy = si.mushroom_policy;
+    let mushroom_input =
+        |val: u32, max: u32, f: fn(&mut MushroomPolicy, u32)| {
+            number_input(val, max, move |nv| {
+                let mut policy = policy;
+                f(&mut policy, nv);
+                Message::SetMushroomPolicy { ident: aid, policy }
+            })
+            .style(iced_aw::NumberInputStyles::Default)
+        };
+
+    let max_per_day =
+        mushroom_input(policy.max_per_day, 999, |p, nv| p.max_per_day = nv);
+    left_col = left_col.push(
+        row!(text("Mushrooms/Day:"), horizontal_space(), max_per_day)
+            .align_items(Alignment::Center),
+    );
+
+    if policy.max_per_day > 0 {
+        let min_missing =
+            mushroom_input(policy.min_missing as u32, 99, |p, nv| {
+                p.min_missing = nv as usize
+            });
+        left_col = left_col.push(
+            row!(text("Min. New Items:"), horizontal_space(), min_missing)
+                .align_items(Alignment::Center),
+        );
+
+        let reserve = mushroom_input(policy.reserve, 99_999, |p, nv| {
+            p.reserve = nv;
+        });
+        left_col = left_col.push(
+            row!(text("Keep Reserve:"), horizontal_space(), reserve)
+                .align_items(Alignment::Center),
+        );
+    }
+
     left_col = left_col.push(button("Copy Optimal Battle Order").on_press(
         Message::CopyBattleOrder {
             ident: player.ident,
@@ -185,6 +226,9 @@ pub fn view_scrapbook<'a>(
         text("Name")
             .width(Length::FillPortion(15))
             .horizontal_alignment(Horizontal::Left),
+        text("")
+            .width(Length::FillPortion(4))
+            .horizontal_alignment(Horizontal::Center),
     ));
     let name_bar = scrollable(name_bar);
 
@@ -209,11 +253,20 @@ pub fn view_scrapbook<'a>(
                 .horizontal_alignment(Horizontal::Left),
         );
 
+        // Targets one of our other characters is going to attack next
+        let claimed = si.claimed.contains(&v.info.uid);
+
         target_list = target_list.push(row!(
-            column!(button("Attack").on_press(Message::PlayerAttack {
-                ident: player.ident,
-                target: v.to_owned()
-            }))
+            column!(button("Attack")
+                .on_press(Message::PlayerAttack {
+                    ident: player.ident,
+                    target: v.to_owned()
+                })
+                .style(if claimed {
+                    theme::Button::Secondary
+                } else {
+                    theme::Button::Primary
+                }))
             .align_items(Alignment::Center)
             .width(Length::FillPortion(5)),
             text(v.missing)
@@ -231,10 +284,83 @@ pub fn view_scrapbook<'a>(
             .width(Length::FillPortion(5))
             .horizontal_alignment(Horizontal::Center),
             target_ident,
+            column!(button("Ban")
+                .on_press(Message::PlayerBlacklistAdd {
+                    ident: player.ident,
+                    uid: v.info.uid,
+                    name: v.info.name.clone(),
+                })
+                .style(theme::Button::Destructive))
+            .align_items(Alignment::Center)
+            .width(Length::FillPortion(4)),
+        ));
+    }
+    let target_list = scrollable(target_list).height(Length::Fill);
+    let mut right_col = column!(name_bar, target_list).spacing(10);
+
+    if !si.blacklist.is_empty() {
+        let mut entries: Vec<_> = si.blacklist.values().collect();
+        entries.sort_by(|a, b| {
+            b.manual
+                .cmp(&a.manual)
+                .then(b.last_loss.cmp(&a.last_loss))
+                .then(a.name.cmp(&b.name))
+        });
+
+        let mut blacklist = column!().spacing(5);
+        blacklist = blacklist.push(row!(
+            text("Blacklist")
+                .size(16)
+                .width(Length::FillPortion(15))
+                .horizontal_alignment(Horizontal::Left),
+            text("Losses")
+                .width(Length::FillPortion(5))
+                .horizontal_alignment(Horizontal::Center),
+            text("Last Loss")
+                .width(Length::FillPortion(8))
+                .horizontal_alignment(Horizontal::Center),
+            text("")
+                .width(Length::FillPortion(5))
+                .horizontal_alignment(Horizontal::Center),
         ));
+
+        for entry in entries {
+            let losses = if entry.manual {
+                format!("{} (Ban)", entry.losses)
+            } else {
+                entry.losses.to_string()
+            };
+            let last_loss = entry
+                .last_loss
+                .map(|a| a.format("%Y-%m-%d").to_string())
+                .unwrap_or("-".to_string());
+
+            blacklist = blacklist.push(
+                row!(
+                    text(&entry.name)
+                        .width(Length::FillPortion(15))
+                        .horizontal_alignment(Horizontal::Left),
+                    text(losses)
+                        .width(Length::FillPortion(5))
+                        .horizontal_alignment(Horizontal::Center),
+                    text(last_loss)
+                        .width(Length::FillPortion(8))
+                        .horizontal_alignment(Horizontal::Center),
+                    column!(button("Pardon").on_press(
+                        Message::PlayerBlacklistRemove {
+                            ident: player.ident,
+                            uid: entry.uid,
+                        }
+                    ))
+                    .align_items(Alignment::Center)
+                    .width(Length::FillPortion(5)),
+                )
+                .align_items(Alignment::Center),
+            );
+        }
+        right_col =
+            right_col.push(scrollable(blacklist).height(Length::Fixed(200.0)));
     }
-    let target_list = scrollable(target_list);
-    let right_col = column!(name_bar, target_list).spacing(10);
 
     row!(
         left_col.width(Length::Fixed(200.0)),
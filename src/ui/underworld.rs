@@ -8,13 +8,16 @@ use iced::{
     Alignment, Element, Length,
 };
 use iced_aw::number_input;
+use num_format::ToFormattedString;
+use sf_api::gamestate::items::EquipmentSlot;
 
-use super::view_crawling;
+use super::{completion::slot_name, view_crawling};
 use crate::{
-    config::Config,
+    config::{Config, NakedLimits},
+    history::HistoryFilter,
     message::Message,
-    player::{AccountInfo, AccountStatus},
-    server::ServerInfo,
+    player::{avg_unit_level, AccountInfo, AccountStatus},
+    server::{CrawlingStatus, ServerInfo},
     ClassImages,
 };
 
@@ -64,14 +67,15 @@ pub fn view_underworld<'a>(
         .horizontal_alignment(Horizontal::Right),
     ));
 
-    let avg_lvl = info
-        .underworld
-        .units
-        .as_array()
-        .iter()
-        .map(|a| a.level as u64)
-        .sum::<u64>() as f32
-        / 3.0;
+    let lures = player.history.lure_stats(&HistoryFilter::default());
+    left_col = left_col.push(row!(
+        text("Lures Won:").width(Length::FillPortion(1)),
+        text(format!("{}/{}", lures.won, lures.total()))
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Right),
+    ));
+
+    let avg_lvl = avg_unit_level(&info.underworld);
     left_col = left_col.push(row!(
         text("Avg Unit Level:").width(Length::FillPortion(1)),
         text(format!("{:.0}", avg_lvl))
@@ -102,6 +106,8 @@ pub fn view_underworld<'a>(
         },
     ));
 
+    left_col = left_col.push(view_naked_rules(server, config));
+
     if !info.attack_log.is_empty() {
         let mut log = column!().padding(5).spacing(5);
 
@@ -134,6 +140,12 @@ pub fn view_underworld<'a>(
         text("Items")
             .width(Length::FillPortion(1))
             .horizontal_alignment(Horizontal::Center),
+        text("Chance")
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Center),
+        text("Lured")
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Center),
         text("Name")
             .width(Length::FillPortion(3))
             .horizontal_alignment(Horizontal::Left),
@@ -141,7 +153,8 @@ pub fn view_underworld<'a>(
     let name_bar = scrollable(name_bar);
 
     let mut target_list = column!().spacing(10);
-    for v in &info.best {
+    for candidate in &info.best {
+        let v = &candidate.info;
         let mut target_ident = row!()
             .align_items(Alignment::Start)
             .spacing(5)
@@ -171,6 +184,8 @@ pub fn view_underworld<'a>(
                         target: LureTarget {
                             uid: v.uid,
                             name: v.name.clone(),
+                            level: v.level,
+                            items: v.equipment.len(),
                         },
                     })
                 }
@@ -183,6 +198,15 @@ pub fn view_underworld<'a>(
             text(v.equipment.len())
                 .width(Length::FillPortion(1))
                 .horizontal_alignment(Horizontal::Center),
+            text(format!("{:.0}%", candidate.success * 100.0))
+                .width(Length::FillPortion(1))
+                .horizontal_alignment(Horizontal::Center),
+            text(match &candidate.previous {
+                Some(prev) => format!("{}/{}", prev.won, prev.total()),
+                None => String::new(),
+            })
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Center),
             target_ident
         ));
     }
@@ -199,8 +223,108 @@ pub fn view_underworld<'a>(
     .into()
 }
 
+/// The settings for which players are considered as lure targets on this
+/// server
+fn view_naked_rules<'a>(
+    server: &'a ServerInfo,
+    config: &'a Config,
+) -> Element<'a, Message> {
+    let applied = config.naked_rules(&server.ident.ident);
+    // Changes are only applied on request, because rebuilding the list of
+    // candidates has to go through every crawled player
+    let rules = server.naked_draft.as_ref().unwrap_or(applied);
+    let limits = rules.limits;
+    let server_id = server.ident.id;
+
+    let mut col = column!().spacing(10);
+    col = col.push(text("Lure Target Rules").size(16));
+
+    if let CrawlingStatus::Crawling { naked, .. } = &server.crawling {
+        let count: usize = naked.values().map(|a| a.len()).sum();
+        col = col.push(row!(
+            text("Candidates:").width(Length::FillPortion(1)),
+            text(count.to_formatted_string(&config.num_format))
+                .width(Length::FillPortion(1))
+                .horizontal_alignment(Horizontal::Right),
+        ));
+    }
+
+    let limit_input =
+        |name, val: u16, max: u16, f: fn(&mut NakedLimits, u16)| {
+            let input = number_input(val, max, move |nv| {
+                let mut limits = limits;
+                f(&mut limits, nv);
+                Message::SetNakedLimits {
+                    server: server_id,
+                    limits,
+                }
+            })
+            .style(iced_aw::NumberInputStyles::Default);
+            row!(text(name), horizontal_space(), input)
+                .align_items(Alignment::Center)
+        };
+
+    col = col.push(limit_input(
+        "Max Items:",
+        limits.max_items as u16,
+        10,
+        |l, nv| l.max_items = nv as usize,
+    ));
+    col = col.push(limit_input(
+        "Min Level:",
+        limits.min_level,
+        9999,
+        |l, nv| l.min_level = nv,
+    ));
+    col = col.push(limit_input(
+        "Max Level (0 = any):",
+        limits.max_level,
+        9999,
+        |l, nv| l.max_level = nv,
+    ));
+
+    col = col.push(text("Must be empty:"));
+    let slots = [
+        EquipmentSlot::Weapon,
+        EquipmentSlot::Shield,
+        EquipmentSlot::Hat,
+        EquipmentSlot::BreastPlate,
+        EquipmentSlot::Gloves,
+        EquipmentSlot::FootWear,
+        EquipmentSlot::Amulet,
+        EquipmentSlot::Belt,
+        EquipmentSlot::Ring,
+        EquipmentSlot::Talisman,
+    ];
+    for pair in slots.chunks(2) {
+        let mut slot_row = row!().spacing(5);
+        for slot in pair {
+            let slot = *slot;
+            slot_row = slot_row.push(
+                checkbox(slot_name(slot), rules.empty_slots.contains(&slot))
+                    .on_toggle(move |empty| Message::SetNakedSlot {
+                        server: server_id,
+                        slot,
+                        empty,
+                    })
+                    .size(14)
+                    .text_size(14)
+                    .width(Length::FillPortion(1)),
+            );
+        }
+        col = col.push(slot_row);
+    }
+    let changed = rules != applied;
+    col = col.push(button("Apply").on_press_maybe(
+        changed.then_some(Message::ApplyNakedRules { server: server_id }),
+    ));
+    col.into()
+}
+
 #[derive(Debug, Clone)]
 pub struct LureTarget {
     pub uid: u32,
     pub name: String,
+    pub level: u16,
+    pub items: usize,
 }
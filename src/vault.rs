@@ -0,0 +1,207 @@
+use std::num::NonZeroU32;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+/// Marks a value in the config as encrypted
+const PREFIX: &str = "enc1:";
+/// The amount of PBKDF2 iterations for new master passwords
+const ITERATIONS: u32 = 600_000;
+/// Encrypted in the header, so that we can tell if a password is wrong, even
+/// if there is nothing else encrypted yet
+const CHECK_VALUE: &str = "sf-scrapbook-helper";
+
+/// Everything, that is needed to derive the key from the master password.
+/// This is stored in the config
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VaultHeader {
+    salt: String,
+    iterations: u32,
+    check: String,
+}
+
+/// The key derived from the master password. This only ever exists in memory
+#[derive(Clone)]
+pub struct Vault {
+    key: [u8; 32],
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Vault")
+    }
+}
+
+#[derive(Debug)]
+pub enum VaultError {
+    WrongPassword,
+    Corrupted,
+    /// The system RNG, or the cipher itself failed
+    Crypto,
+}
+
+impl std::fmt::Display for VaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VaultError::WrongPassword => "Wrong master password",
+            VaultError::Corrupted => "The encrypted config is corrupted",
+            VaultError::Crypto => "Could not encrypt the passwords",
+        })
+    }
+}
+
+impl std::error::Error for VaultError {
+}
+
+impl Vault {
+    /// Creates a new vault with a random salt for the password
+    pub fn create(password: &str) -> Result<(Vault, VaultHeader), VaultError> {
+        let mut salt = [0; 16];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| VaultError::Crypto)?;
+        let vault = Vault::derive(password, &salt, ITERATIONS);
+        let header = VaultHeader {
+            salt: STANDARD.encode(salt),
+            iterations: ITERATIONS,
+            check: vault.encrypt(CHECK_VALUE)?,
+        };
+        Ok((vault, header))
+    }
+
+    /// Derives the key from the password and checks, that it is the correct
+    /// one
+    pub fn unlock(
+        password: &str,
+        header: &VaultHeader,
+    ) -> Result<Vault, VaultError> {
+        let salt = STANDARD
+            .decode(&header.salt)
+            .map_err(|_| VaultError::Corrupted)?;
+        let vault = Vault::derive(password, &salt, header.iterations);
+        match vault.decrypt(&header.check) {
+            Ok(check) if check == CHECK_VALUE => Ok(vault),
+            _ => Err(VaultError::WrongPassword),
+        }
+    }
+
+    fn derive(password: &str, salt: &[u8], iterations: u32) -> Vault {
+        let mut key = [0; 32];
+        let iterations = NonZeroU32::new(iterations).unwrap_or(NonZeroU32::MIN);
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            password.as_bytes(),
+            &mut key,
+        );
+        Vault { key }
+    }
+
+    fn key(&self) -> LessSafeKey {
+        // This can only fail, if the key length is wrong, which it never is
+        #[allow(clippy::unwrap_used)]
+        LessSafeKey::new(
+            UnboundKey::new(&CHACHA20_POLY1305, &self.key).unwrap(),
+        )
+    }
+
+    pub fn encrypt(&self, plain: &str) -> Result<String, VaultError> {
+        // A failed RNG would leave the nonce at zero, which must never be
+        // reused with the same key
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| VaultError::Crypto)?;
+        let mut data = plain.as_bytes().to_vec();
+        self.key()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut data,
+            )
+            .map_err(|_| VaultError::Crypto)?;
+        let mut out = nonce.to_vec();
+        out.extend(data);
+        Ok(format!("{PREFIX}{}", STANDARD.encode(out)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, VaultError> {
+        let data = value
+            .strip_prefix(PREFIX)
+            .and_then(|a| STANDARD.decode(a).ok())
+            .filter(|a| a.len() > NONCE_LEN)
+            .ok_or(VaultError::Corrupted)?;
+        let (nonce, data) = data.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| VaultError::Corrupted)?;
+        let mut data = data.to_vec();
+        let plain = self
+            .key()
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| VaultError::WrongPassword)?;
+        String::from_utf8(plain.to_vec()).map_err(|_| VaultError::Corrupted)
+    }
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (vault, header) = Vault::create("master").unwrap();
+        let encrypted = vault.encrypt("secret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("secret"));
+        // Every value gets its own nonce
+        assert_ne!(encrypted, vault.encrypt("secret").unwrap());
+
+        let unlocked = Vault::unlock("master", &header).unwrap();
+        assert_eq!(unlocked.decrypt(&encrypted).unwrap(), "secret");
+    }
+
+    #[test]
+    fn wrong_password() {
+        let (vault, header) = Vault::create("master").unwrap();
+        let encrypted = vault.encrypt("secret").unwrap();
+
+        assert!(matches!(
+            Vault::unlock("other", &header),
+            Err(VaultError::WrongPassword)
+        ));
+        let (other, _) = Vault::create("other").unwrap();
+        assert!(matches!(
+            other.decrypt(&encrypted),
+            Err(VaultError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn corrupted_value() {
+        let (vault, _) = Vault::create("master").unwrap();
+        let encrypted = vault.encrypt("secret").unwrap();
+
+        for value in ["secret", "enc1:", "enc1:not base64!", "enc1:AAAA"] {
+            assert!(
+                matches!(vault.decrypt(value), Err(VaultError::Corrupted)),
+                "{value}"
+            );
+        }
+
+        // Changed data fails the authentication of the cipher
+        let mut data = STANDARD.decode(&encrypted[PREFIX.len()..]).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        let tampered = format!("{PREFIX}{}", STANDARD.encode(data));
+        assert!(vault.decrypt(&tampered).is_err());
+    }
+}